@@ -0,0 +1,42 @@
+use cosmwasm_std::{Addr, Deps, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::{IsAllowedResponse, RegistryQueryMsg};
+use crate::state::{AccessMode, Collection, ALLOWLIST};
+
+/// Whether `address` may lend against or borrow from `collection`.
+pub fn is_allowed(
+    deps: Deps,
+    collection_id: Uint128,
+    collection: &Collection,
+    address: &Addr,
+) -> Result<bool, ContractError> {
+    match &collection.access {
+        AccessMode::Open => Ok(true),
+        AccessMode::Allowlist => Ok(ALLOWLIST.has(deps.storage, (collection_id.u128(), address))),
+        AccessMode::Registry { contract } => {
+            let res: IsAllowedResponse = deps.querier.query_wasm_smart(
+                contract,
+                &RegistryQueryMsg::IsAllowed {
+                    collection: collection.addr.to_string(),
+                    address: address.to_string(),
+                },
+            )?;
+            Ok(res.allowed)
+        }
+    }
+}
+
+pub fn ensure_allowed(
+    deps: Deps,
+    collection_id: Uint128,
+    collection: &Collection,
+    address: &Addr,
+) -> Result<(), ContractError> {
+    if !is_allowed(deps, collection_id, collection, address)? {
+        return Err(ContractError::NotAllowed {
+            address: address.clone(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use cosmwasm_std::{coins, from_json, to_json_binary, DepsMut, Env, MessageInfo, Response, Uint128};
+use cosmwasm_schema::serde::de::DeserializeOwned;
+
+use crate::contract::{action_event, claim, lend, repay, withdraw};
+use crate::error::ContractError;
+use crate::msg::{
+    BatchClaimResponse, BatchLendResponse, BatchRepayResponse, BatchWithdrawResponse, LendItem, OfferRef, RepayItem,
+};
+
+/// Most items a single batch message may carry.
+pub const MAX_BATCH: usize = 50;
+
+// Batches run the single item handlers one after another on the same storage.
+// Any failing item fails the whole message, so either every item is applied or
+// none is.
+
+pub fn batch_lend(mut deps: DepsMut, env: Env, info: MessageInfo, items: Vec<LendItem>) -> Result<Response, ContractError> {
+    check_size(items.len())?;
+    check_funds(&info, items.iter().map(|item| item.amount))?;
+
+    let mut response = Response::new().add_event(action_event("batch_lend").add_attribute("items", items.len().to_string()));
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        let res = lend(
+            deps.branch(),
+            env.clone(),
+            item_info(&info, item.amount),
+            item.collection_id,
+            item.duration,
+            item.auto_relend,
+        )?;
+        results.push(item_data(&res)?);
+        response = merge(response, res);
+    }
+    Ok(response.set_data(to_json_binary(&BatchLendResponse { results })?))
+}
+
+pub fn batch_withdraw(mut deps: DepsMut, env: Env, info: MessageInfo, offers: Vec<OfferRef>) -> Result<Response, ContractError> {
+    check_size(offers.len())?;
+    check_funds(&info, std::iter::empty())?;
+
+    let mut response = Response::new().add_event(action_event("batch_withdraw").add_attribute("items", offers.len().to_string()));
+    let mut results = Vec::with_capacity(offers.len());
+    for offer in offers {
+        let res = withdraw(deps.branch(), env.clone(), item_info(&info, Uint128::zero()), offer.collection_id, offer.offer_id)?;
+        results.push(item_data(&res)?);
+        response = merge(response, res);
+    }
+    Ok(response.set_data(to_json_binary(&BatchWithdrawResponse { results })?))
+}
+
+pub fn batch_repay(mut deps: DepsMut, env: Env, info: MessageInfo, items: Vec<RepayItem>) -> Result<Response, ContractError> {
+    check_size(items.len())?;
+    check_funds(&info, items.iter().map(|item| item.amount))?;
+
+    let mut response = Response::new().add_event(action_event("batch_repay").add_attribute("items", items.len().to_string()));
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        let res = repay(deps.branch(), env.clone(), item_info(&info, item.amount), item.collection_id, item.offer_id)?;
+        results.push(item_data(&res)?);
+        response = merge(response, res);
+    }
+    Ok(response.set_data(to_json_binary(&BatchRepayResponse { results })?))
+}
+
+pub fn batch_claim(mut deps: DepsMut, env: Env, info: MessageInfo, offers: Vec<OfferRef>) -> Result<Response, ContractError> {
+    check_size(offers.len())?;
+    check_funds(&info, std::iter::empty())?;
+
+    let mut response = Response::new().add_event(action_event("batch_claim").add_attribute("items", offers.len().to_string()));
+    let mut results = Vec::with_capacity(offers.len());
+    for offer in offers {
+        let res = claim(deps.branch(), env.clone(), item_info(&info, Uint128::zero()), offer.collection_id, offer.offer_id)?;
+        results.push(item_data(&res)?);
+        response = merge(response, res);
+    }
+    Ok(response.set_data(to_json_binary(&BatchClaimResponse { results })?))
+}
+
+fn check_size(size: usize) -> Result<(), ContractError> {
+    if size == 0 || size > MAX_BATCH {
+        return Err(ContractError::BatchSize { size, max: MAX_BATCH });
+    }
+    Ok(())
+}
+
+/// Items are paid in INJ, the funds sent must match their total exactly.
+fn check_funds(info: &MessageInfo, amounts: impl Iterator<Item = Uint128>) -> Result<(), ContractError> {
+    let mut expected = Uint128::zero();
+    for amount in amounts {
+        expected = expected.checked_add(amount).map_err(|_| ContractError::InterestOverflow {})?;
+    }
+    let mut received = Uint128::zero();
+    for coin in info.funds.iter() {
+        if coin.denom != "inj" {
+            return Err(ContractError::FundsMismatch {
+                denom: coin.denom.clone(),
+                expected: Uint128::zero(),
+                received: coin.amount,
+            });
+        }
+        received = received.checked_add(coin.amount).map_err(|_| ContractError::InterestOverflow {})?;
+    }
+    if received != expected {
+        return Err(ContractError::FundsMismatch { denom: "inj".to_string(), expected, received });
+    }
+    Ok(())
+}
+
+/// The batch sender paying `amount` for a single item.
+fn item_info(info: &MessageInfo, amount: Uint128) -> MessageInfo {
+    MessageInfo {
+        sender: info.sender.clone(),
+        funds: if amount.is_zero() { vec![] } else { coins(amount.u128(), "inj") },
+    }
+}
+
+fn item_data<T: DeserializeOwned>(res: &Response) -> Result<T, ContractError> {
+    Ok(from_json(res.data.clone().unwrap_or_default())?)
+}
+
+fn merge(batch: Response, item: Response) -> Response {
+    batch
+        .add_submessages(item.messages)
+        .add_events(item.events)
+        .add_attributes(item.attributes)
+}
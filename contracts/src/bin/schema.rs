@@ -1,11 +1,35 @@
-use cosmwasm_schema::write_api;
+use std::env::current_dir;
 
-use injective_bank::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use cosmwasm_schema::{export_schema_with_title, schema_for, write_api};
+
+use injective_bank::msg::{
+    AddCollectionResponse, BatchClaimResponse, BatchLendResponse, BatchRepayResponse, BatchWithdrawResponse, BorrowResponse, ClaimResponse, ExecuteMsg, InstantiateMsg, LendResponse,
+    MigrateMsg, PayInstallmentResponse, QueryMsg, RepayResponse, SudoMsg, UpdateOfferResponse, WithdrawResponse,
+};
 
 fn main() {
     write_api! {
         instantiate: InstantiateMsg,
         query: QueryMsg,
         execute: ExecuteMsg,
+        migrate: MigrateMsg,
+        sudo: SudoMsg,
     }
-}
\ No newline at end of file
+
+    // execute responses are set as `data`, write_api! only covers queries
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    out_dir.push("raw");
+    export_schema_with_title(&schema_for!(AddCollectionResponse), &out_dir, "response_to_add_collection");
+    export_schema_with_title(&schema_for!(LendResponse), &out_dir, "response_to_lend");
+    export_schema_with_title(&schema_for!(BorrowResponse), &out_dir, "response_to_borrow");
+    export_schema_with_title(&schema_for!(RepayResponse), &out_dir, "response_to_repay");
+    export_schema_with_title(&schema_for!(PayInstallmentResponse), &out_dir, "response_to_pay_installment");
+    export_schema_with_title(&schema_for!(ClaimResponse), &out_dir, "response_to_claim");
+    export_schema_with_title(&schema_for!(UpdateOfferResponse), &out_dir, "response_to_update_offer");
+    export_schema_with_title(&schema_for!(WithdrawResponse), &out_dir, "response_to_withdraw");
+    export_schema_with_title(&schema_for!(BatchLendResponse), &out_dir, "response_to_batch_lend");
+    export_schema_with_title(&schema_for!(BatchWithdrawResponse), &out_dir, "response_to_batch_withdraw");
+    export_schema_with_title(&schema_for!(BatchRepayResponse), &out_dir, "response_to_batch_repay");
+    export_schema_with_title(&schema_for!(BatchClaimResponse), &out_dir, "response_to_batch_claim");
+}
@@ -0,0 +1,62 @@
+use cosmwasm_std::{to_json_binary, Addr, Empty, Uint128, WasmMsg};
+use cw721_base::msg::ExecuteMsg as Cw721ExecuteMsg;
+
+use crate::error::ContractError;
+use crate::msg::Cw1155ExecuteMsg;
+use crate::state::{Collection, Offer, TokenStandard};
+
+/// Moves a cw721 token to `recipient`. Pulling into escrow needs the bank to be
+/// approved for the token.
+///
+/// cw1155 collateral is pushed by its owner instead, see `Cw1155HookMsg`.
+pub fn transfer_cw721(collection: &Addr, token_id: &str, recipient: &Addr) -> Result<WasmMsg, ContractError> {
+    let transfer = Cw721ExecuteMsg::<Empty, Empty>::TransferNft {
+        recipient: recipient.to_string(),
+        token_id: token_id.to_string(),
+    };
+    Ok(WasmMsg::Execute {
+        contract_addr: collection.to_string(),
+        msg: to_json_binary(&transfer)?,
+        funds: Vec::new(),
+    })
+}
+
+/// Moves all collateral of `offer`, bundled tokens included, out of escrow to `recipient`.
+pub fn release(
+    collection: &Collection,
+    offer: &Offer,
+    escrow: &Addr,
+    recipient: &Addr,
+) -> Result<Vec<WasmMsg>, ContractError> {
+    let mut msgs = vec![release_token(collection, offer, escrow, recipient)?];
+    for item in offer.bundle.iter() {
+        msgs.push(transfer_cw721(&item.collection, &item.token_id, recipient)?);
+    }
+    Ok(msgs)
+}
+
+fn release_token(
+    collection: &Collection,
+    offer: &Offer,
+    escrow: &Addr,
+    recipient: &Addr,
+) -> Result<WasmMsg, ContractError> {
+    let token_id = offer.token_id.clone().unwrap_or_else(|| "None".to_string());
+    let msg = match collection.standard {
+        TokenStandard::Cw721 => to_json_binary(&Cw721ExecuteMsg::<Empty, Empty>::TransferNft {
+            recipient: recipient.to_string(),
+            token_id,
+        })?,
+        TokenStandard::Cw1155 => to_json_binary(&Cw1155ExecuteMsg::BatchSendFrom {
+            from: escrow.to_string(),
+            to: recipient.to_string(),
+            batch: vec![(token_id, offer.token_amount.unwrap_or(Uint128::one()))],
+            msg: None,
+        })?,
+    };
+    Ok(WasmMsg::Execute {
+        contract_addr: collection.addr.to_string(),
+        msg,
+        funds: Vec::new(),
+    })
+}
@@ -1,25 +1,49 @@
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{Status, Offer, OFFERS, OWNER, COLLECTIONS, FOUNDERS, CollectionData, Collection, OfferData};
+use crate::msg::{
+    ExecuteMsg, BundleToken, Fill, Cw1155HookMsg, Cw1155ReceiveMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg,
+    IsAllowedResponse, BlockedTokensResponse, PauseStatusResponse, RolesResponse, AddCollectionResponse,
+    LendResponse, BorrowResponse, RepayResponse, PayInstallmentResponse, ClaimResponse, UpdateOfferResponse,
+    WithdrawResponse, LoanScheduleResponse, PortfolioResponse, CollectionPortfolio, LoansExpiringResponse,
+    ExpiringLoan, CollectionStatsResponse, OfferRef
+};
+use crate::access::{ensure_allowed, is_allowed};
+use crate::batch::{batch_claim, batch_lend, batch_repay, batch_withdraw};
+use crate::collateral::{release, transfer_cw721};
+use crate::keeper::begin_blocker;
+use crate::interest::{bps_rate, interest, late_penalty};
+use crate::lifecycle::transition;
+use crate::listing::verify_collection;
+use crate::oracle::{check_ltv, loan_cap};
+use crate::schedule::amortize;
+use crate::stats::{record_claim, record_default, record_payment, record_repaid, record_taken, replay};
+use crate::state::{
+    AccessMode, ALLOWLIST, BLOCKLIST, Params, PARAMS, Operation, PauseState, PAUSE, Role, ROLES, FeeConfig, FEES,
+    Transition, Valuation, PriceSource, BPS_DENOMINATOR, MAX_APY_BPS, Status, Offer, OFFERS, OWNER, COLLECTIONS,
+    FOUNDERS, CollectionData, Collection, OfferData, TokenStandard, BundleItem, InterestMode, Installment,
+    MATURITIES, OPEN_OFFERS, GRACE_ENDS, CollectionStats, STATS, LatePenalty, AutoRelend, COLLECTIONS_KEY
+};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response,
-    Uint128, Timestamp, BankMsg, coins, WasmMsg
+    from_json, to_json_binary, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order, Response, Storage,
+    Uint128, Timestamp, BankMsg, coins, Event
 };
-use rust_decimal::Decimal;
-use num_traits::{pow::Pow, ToPrimitive};
-use std::str::FromStr;
-use cw2::set_contract_version;
-use cw721_base::{
-    msg::ExecuteMsg as Cw721ExecuteMsg,
-};
-use cw_utils::must_pay;
+use cw2::{get_contract_version, set_contract_version};
+use cosmwasm_schema::cw_serde;
+use cw_storage_plus::{Bound, Item};
+use cw_utils::{must_pay, nonpayable};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:injective-bank";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// pagination defaults for list queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// custom event type, the chain exposes it to indexers as `wasm-injective-bank`
+pub const EVENT_TYPE: &str = "injective-bank";
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -31,10 +55,18 @@ pub fn instantiate(
 
     OWNER.save(deps.storage, &info.sender.clone())?;
     COLLECTIONS.save(deps.storage, &Vec::new().clone())?;
-    let mut founders: Vec<Addr> = Vec::new();
-    founders.push(msg.founder_one);
-    founders.push(msg.founder_two);
-    FOUNDERS.save(deps.storage, &founders.clone());
+    let founders: Vec<Addr> = vec![
+        deps.api.addr_validate(&msg.founder_one)?,
+        deps.api.addr_validate(&msg.founder_two)?,
+    ];
+    FOUNDERS.save(deps.storage, &founders)?;
+    ROLES.save(deps.storage, &info.sender, &vec![Role::Admin])?;
+    if let Some(guardian) = msg.guardian {
+        let guardian = deps.api.addr_validate(&guardian)?;
+        add_role(deps.storage, &guardian, Role::Guardian)?;
+    }
+    PAUSE.save(deps.storage, &PauseState::default())?;
+    FEES.save(deps.storage, &FeeConfig::default())?;
 
     Ok(Response::new()
         .add_attribute("instantiated", "true"))
@@ -51,15 +83,100 @@ pub fn execute(
     match msg {
         AddCollection {
             collection_address,
-            apy
+            apy_bps,
+            standard,
+            interest_mode,
+        } => {
+            let standard = standard.unwrap_or_default();
+            let interest_mode = interest_mode.unwrap_or_default();
+            Ok(add_collection(deps, env, info, collection_address, apy_bps, standard, interest_mode)?)
+        },
+        SetValuation {
+            collection_id,
+            valuation,
+        } => {
+            Ok(set_valuation(deps, env, info, collection_id, valuation)?)
+        },
+        SetLatePenalty {
+            collection_id,
+            penalty,
+        } => {
+            Ok(set_late_penalty(deps, env, info, collection_id, penalty)?)
+        },
+        SetAccessMode {
+            collection_id,
+            mode,
+        } => {
+            Ok(set_access_mode(deps, env, info, collection_id, mode)?)
+        },
+        UpdateAllowlist {
+            collection_id,
+            add,
+            remove,
+        } => {
+            Ok(update_allowlist(deps, env, info, collection_id, add, remove)?)
+        },
+        UpdateBlocklist {
+            collection_id,
+            block,
+            unblock,
+        } => {
+            Ok(update_blocklist(deps, env, info, collection_id, block, unblock)?)
+        },
+        GrantRole {
+            address,
+            role,
+        } => {
+            Ok(grant_role(deps, env, info, address, role)?)
+        },
+        RevokeRole {
+            address,
+            role,
+        } => {
+            Ok(revoke_role(deps, env, info, address, role)?)
+        },
+        UpdateFees {
+            founder_fee_bps,
+            penalty_fee_bps,
+        } => {
+            Ok(update_fees(deps, env, info, founder_fee_bps, penalty_fee_bps)?)
+        },
+        Pause {
+            operations,
+        } => {
+            Ok(set_paused(deps, env, info, operations, true)?)
+        },
+        Unpause {
+            operations,
+        } => {
+            Ok(set_paused(deps, env, info, operations, false)?)
+        },
+        SetEmergency {
+            enabled,
         } => {
-            Ok(add_collection(deps, env, info, collection_address, apy)?)
+            Ok(set_emergency(deps, env, info, enabled)?)
         },
         Lend {
             collection_id,
             duration,
+            auto_relend,
+        } => {
+            Ok(lend(deps, env, info, collection_id, duration, auto_relend)?)
+        },
+        UpdateOffer {
+            collection_id,
+            offer_id,
+            new_amount,
+            new_duration,
+            new_interest,
+        } => {
+            Ok(update_offer(deps, env, info, collection_id, offer_id, new_amount, new_duration, new_interest)?)
+        },
+        StopAutoRelend {
+            collection_id,
+            offer_id,
         } => {
-            Ok(lend(deps, env, info, collection_id, duration)?)
+            Ok(stop_auto_relend(deps, env, info, collection_id, offer_id)?)
         },
         Borrow {
             collection_id,
@@ -79,6 +196,55 @@ pub fn execute(
         } => {
             Ok(claim(deps, env, info, collection_id, offer_id)?)
         },
+        BorrowBundle {
+            collection_id,
+            tokens,
+        } => {
+            Ok(borrow_bundle(deps, env, info, collection_id, tokens)?)
+        },
+        BorrowAggregated {
+            collection_id,
+            token_id,
+            fill,
+        } => {
+            Ok(borrow_aggregated(deps, env, info, collection_id, token_id, fill)?)
+        },
+        BorrowInstallments {
+            collection_id,
+            token_id,
+            installments,
+        } => {
+            Ok(borrow_installments(deps, env, info, collection_id, token_id, installments)?)
+        },
+        PayInstallment {
+            collection_id,
+            offer_id,
+        } => {
+            Ok(pay_installment(deps, env, info, collection_id, offer_id)?)
+        },
+        BatchLend {
+            items,
+        } => {
+            Ok(batch_lend(deps, env, info, items)?)
+        },
+        BatchWithdraw {
+            offers,
+        } => {
+            Ok(batch_withdraw(deps, env, info, offers)?)
+        },
+        BatchRepay {
+            items,
+        } => {
+            Ok(batch_repay(deps, env, info, items)?)
+        },
+        BatchClaim {
+            offers,
+        } => {
+            Ok(batch_claim(deps, env, info, offers)?)
+        },
+        Receive(msg) => {
+            Ok(receive_cw1155(deps, env, info, msg)?)
+        },
         Withdraw {
             collection_id,
             offer_id
@@ -92,579 +258,4164 @@ pub fn add_collection(
     deps: DepsMut, 
     _env: Env, 
     info: MessageInfo, 
-    collection_address: Addr,
-    apy: Uint128,
+    collection_address: String,
+    apy_bps: u32,
+    standard: TokenStandard,
+    interest_mode: InterestMode,
 ) -> Result<Response, ContractError> {
-    let owner : Addr= OWNER.load(deps.storage)?;
-    if owner != info.sender {
-        return Err(ContractError::NotOwner {});
+    ensure_role(deps.as_ref(), &info.sender, Role::Admin)?;
+    let collection_address = deps.api.addr_validate(&collection_address)?;
+    if apy_bps == 0 || apy_bps > MAX_APY_BPS {
+        return Err(ContractError::InvalidApy { apy_bps, max: MAX_APY_BPS });
     }
 
     let mut collections = COLLECTIONS.load(deps.storage)?;
+    // offers are keyed by address, listing twice would wipe them
+    if collections.iter().any(|c| c.addr == collection_address) {
+        return Err(ContractError::CollectionExists { address: collection_address });
+    }
+    let params = PARAMS.may_load(deps.storage)?.unwrap_or_default();
+    let info = verify_collection(deps.as_ref(), &collection_address, standard, &params)?;
+
     let data = Collection {
         addr: collection_address.clone(),
-        apy: apy.clone()
+        name: info.name,
+        symbol: info.symbol,
+        apy_bps,
+        valuation: None,
+        access: AccessMode::Open,
+        standard,
+        interest_mode,
+        late_penalty: None,
     };
     collections.push(data.clone());
+    let collection_id = Uint128::from((collections.len() - 1) as u128);
 
     COLLECTIONS.save(deps.storage, &collections)?;
     OFFERS.save(deps.storage, collection_address.clone(), &Vec::new())?;
-    Ok(Response::default())
+    Ok(Response::new()
+        .add_event(action_event("add_collection")
+            .add_attribute("collection_id", collection_id)
+            .add_attribute("address", collection_address))
+        .set_data(to_json_binary(&AddCollectionResponse { collection_id })?))
 }
 
-pub fn lend(
-    deps: DepsMut, 
-    _env: Env, 
-    info: MessageInfo, 
-    collection_id: Uint128, 
-    duration: Uint128, 
+pub fn set_valuation(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    valuation: Option<Valuation>,
 ) -> Result<Response, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
+    ensure_role(deps.as_ref(), &info.sender, Role::RiskManager)?;
 
-    if collections.get(collection_id.u128() as usize).is_none() {
-        return Err(ContractError::InvalidCollectionId {collection_id});
-    }
+    let valuation = match valuation {
+        Some(v) => {
+            if u128::from(v.max_ltv_bps) > BPS_DENOMINATOR {
+                return Err(ContractError::InvalidLtv { max_ltv_bps: v.max_ltv_bps });
+            }
+            let source = match v.source {
+                PriceSource::Oracle { contract } => PriceSource::Oracle {
+                    contract: deps.api.addr_validate(contract.as_str())?,
+                },
+                fixed => fixed,
+            };
+            Some(Valuation { source, max_ltv_bps: v.max_ltv_bps })
+        },
+        None => None,
+    };
 
-    let collection = &collections[collection_id.u128() as usize];
+    update_collection(deps.storage, collection_id, |c| c.valuation = valuation)?;
 
-    let value = must_pay(&info, "inj")?.u128();
-    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
-    let founders = FOUNDERS.load(deps.storage)?;
-    let base_amount = (value) as u128;
-    let percentage: u128 = 5;
-    let divisor: u128 = 1000;
+    Ok(Response::new()
+        .add_event(action_event("set_valuation")
+            .add_attribute("collection_id", collection_id)))
+}
 
-    // Perform the multiplication and division
-    let commission = (base_amount) * percentage / divisor;
-    let transfer_msg_one = BankMsg::Send {
-        to_address: founders[0].clone().into(),
-        amount: coins(commission.into(), "inj"),
-    };
-    let transfer_msg_two = BankMsg::Send {
-        to_address: founders[1].clone().into(),
-        amount: coins(commission.into(), "inj"),
-    };
-    let new_offer = Offer {
-        offer_id: Uint128::from(offers.len() as u128),
-        amount: Uint128::from(value - ((2*commission) as u128)),
-        active_till: Timestamp::from_nanos(0),
-        lender: info.sender.clone(),
-        status: Status::Open,
-        borrower: None,
-        interest: Uint128::from(get_interest(collection.apy.clone().u128(), duration.clone().u128(), value)),
-        token_id: None,
-        duration: Uint128::from(duration),
-    };
-    offers.push(new_offer.clone());
-    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+pub fn set_late_penalty(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    penalty: Option<LatePenalty>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::RiskManager)?;
+    update_collection(deps.storage, collection_id, |c| c.late_penalty = penalty)?;
 
-    Ok(Response::new().add_message(transfer_msg_one).add_message(transfer_msg_two))
+    Ok(Response::new()
+        .add_event(action_event("set_late_penalty")
+            .add_attribute("collection_id", collection_id)))
 }
 
-fn get_interest(apy: u128, duration: u128, amount: u128) -> u128 {
-    let apy_decimal = u128_to_decimal(apy);
-    let duration_decimal = u128_to_decimal(duration);
-    let amount_decimal = u128_to_decimal(amount);
-    let t_int = ((apy_decimal / Decimal::from_str("100").unwrap() + Decimal::from(1))
-        .pow(duration_decimal / Decimal::from_str("31536000").unwrap()) - Decimal::from(1))*(Decimal::from(31536000) / duration_decimal);
+pub fn set_access_mode(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    mode: AccessMode,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Admin)?;
 
-    let an_int = t_int * amount_decimal;
+    let mode = match mode {
+        AccessMode::Registry { contract } => AccessMode::Registry {
+            contract: deps.api.addr_validate(contract.as_str())?,
+        },
+        other => other,
+    };
+    update_collection(deps.storage, collection_id, |c| c.access = mode)?;
 
-    (an_int * (duration_decimal / Decimal::from(31536000))).to_u128().unwrap()
+    Ok(Response::new()
+        .add_event(action_event("set_access_mode")
+            .add_attribute("collection_id", collection_id)))
 }
 
-fn u128_to_decimal(value: u128) -> Decimal {
-    Decimal::from_str(&value.to_string()).unwrap()
+pub fn update_allowlist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Admin)?;
+    load_collection(deps.as_ref(), collection_id)?;
+
+    for address in add.iter() {
+        let address = deps.api.addr_validate(address)?;
+        ALLOWLIST.save(deps.storage, (collection_id.u128(), &address), &Empty {})?;
+    }
+    for address in remove.iter() {
+        let address = deps.api.addr_validate(address)?;
+        ALLOWLIST.remove(deps.storage, (collection_id.u128(), &address));
+    }
+
+    Ok(Response::new()
+        .add_event(action_event("update_allowlist")
+            .add_attribute("collection_id", collection_id)
+            .add_attribute("added", add.len().to_string())
+            .add_attribute("removed", remove.len().to_string())))
 }
 
-pub fn borrow(
-    deps: DepsMut, 
-    env: Env, 
-    info: MessageInfo, 
-    collection_id: Uint128, 
-    token_id: Uint128, 
+pub fn update_blocklist(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    block: Vec<String>,
+    unblock: Vec<String>,
 ) -> Result<Response, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
+    ensure_role(deps.as_ref(), &info.sender, Role::RiskManager)?;
+    load_collection(deps.as_ref(), collection_id)?;
 
-    if collections.get(collection_id.u128() as usize).is_none() {
-        return Err(ContractError::InvalidCollectionId {collection_id});
+    for token_id in block.iter() {
+        BLOCKLIST.save(deps.storage, (collection_id.u128(), token_id), &Empty {})?;
+    }
+    for token_id in unblock.iter() {
+        BLOCKLIST.remove(deps.storage, (collection_id.u128(), token_id));
     }
 
-    let collection = &collections[collection_id.u128() as usize];
-    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    Ok(Response::new()
+        .add_event(action_event("update_blocklist")
+            .add_attribute("collection_id", collection_id)
+            .add_attribute("blocked", block.len().to_string())
+            .add_attribute("unblocked", unblock.len().to_string())))
+}
 
+fn ensure_not_blocked(deps: Deps, collection_id: Uint128, token_id: &str) -> Result<(), ContractError> {
+    if BLOCKLIST.has(deps.storage, (collection_id.u128(), token_id)) {
+        return Err(ContractError::TokenBlocked { token_id: token_id.to_string() });
+    }
+    Ok(())
+}
 
-    let best_offer: Uint128 = get_best_offer(deps.as_ref(), collection_id, info.sender.clone())?;
-    let curr_offer = &offers[(best_offer).u128() as usize];
+fn update_collection(
+    storage: &mut dyn Storage,
+    collection_id: Uint128,
+    update: impl FnOnce(&mut Collection),
+) -> Result<(), ContractError> {
+    let mut collections = COLLECTIONS.load(storage)?;
+    let collection = usize::try_from(collection_id.u128())
+        .ok()
+        .and_then(|i| collections.get_mut(i))
+        .ok_or(ContractError::InvalidCollectionId { collection_id })?;
+    update(collection);
+    COLLECTIONS.save(storage, &collections)?;
+    Ok(())
+}
 
-    if curr_offer.lender.clone() == info.sender.clone() {
-        return Err(ContractError::IsLender {});
+fn add_role(storage: &mut dyn Storage, address: &Addr, role: Role) -> Result<(), ContractError> {
+    let mut roles = ROLES.may_load(storage, address)?.unwrap_or_default();
+    if !roles.contains(&role) {
+        roles.push(role);
     }
+    ROLES.save(storage, address, &roles)?;
+    Ok(())
+}
 
-    let transfer_msg = BankMsg::Send {
-        to_address: info.sender.clone().into(),
-        amount: coins((curr_offer.amount).into(), "inj"),
-    };
-
-    let transfer: Cw721ExecuteMsg::<Empty, Empty> = Cw721ExecuteMsg::<Empty, Empty>::TransferNft {
-        recipient: env.contract.address.to_string(),
-        token_id: String::from(token_id),
-    };
-    let wasm_msg = WasmMsg::Execute {
-        contract_addr: collection.addr.to_string(),
-        msg: to_json_binary(&transfer)?,
-        funds: Vec::new(),
-    };
+fn ensure_role(deps: Deps, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    let roles = ROLES.may_load(deps.storage, sender)?.unwrap_or_default();
+    if roles.contains(&role) || roles.contains(&Role::Admin) {
+        return Ok(());
+    }
+    Err(ContractError::Unauthorized { role })
+}
 
-    let updated_offer = Offer {
-        offer_id: curr_offer.offer_id,
-        amount: curr_offer.amount,
-        active_till: Timestamp::from_nanos(env.block.time.nanos() + ((curr_offer.duration.u128() as u64) * 1_000_000_000)),
-        lender: curr_offer.lender.clone(),
-        interest: curr_offer.interest,
-        status: Status::Taken,
-        borrower: Some(info.sender.clone()),
-        token_id: Some(token_id),
-        duration: curr_offer.duration,
-    };
+pub fn grant_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Admin)?;
 
-    offers[(best_offer).u128() as usize] = updated_offer;
-    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
-    
-    Ok(Response::new().add_message(transfer_msg).add_message(wasm_msg))
+    let address = deps.api.addr_validate(&address)?;
+    add_role(deps.storage, &address, role)?;
 
+    Ok(Response::new()
+        .add_event(action_event("grant_role")
+            .add_attribute("address", address)
+            .add_attribute("role", role.to_string())))
 }
 
-pub fn repay(
-    deps: DepsMut, 
-    env: Env, 
-    info: MessageInfo, 
-    collection_id: Uint128, 
-    offer_id: Uint128, 
+pub fn revoke_role(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+    role: Role,
 ) -> Result<Response, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
+    ensure_role(deps.as_ref(), &info.sender, Role::Admin)?;
 
-    if collections.get(collection_id.u128() as usize).is_none() {
-        return Err(ContractError::InvalidCollectionId {collection_id});
+    let address = deps.api.addr_validate(&address)?;
+    if role == Role::Admin {
+        let admins = ROLES
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|item| matches!(item, Ok((_, roles)) if roles.contains(&Role::Admin)))
+            .count();
+        let is_admin = ROLES.may_load(deps.storage, &address)?.unwrap_or_default().contains(&Role::Admin);
+        if is_admin && admins <= 1 {
+            return Err(ContractError::LastAdmin {});
+        }
     }
 
-    let collection = &collections[collection_id.u128() as usize];
-    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let mut roles = ROLES.may_load(deps.storage, &address)?.unwrap_or_default();
+    roles.retain(|r| *r != role);
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, &address);
+    } else {
+        ROLES.save(deps.storage, &address, &roles)?;
+    }
 
+    Ok(Response::new()
+        .add_event(action_event("revoke_role")
+            .add_attribute("address", address)
+            .add_attribute("role", role.to_string())))
+}
 
-    let curr_offer = &offers[offer_id.u128() as usize];
-    let borrower = match curr_offer.borrower.clone() {
-        Some(addr) => addr.clone(),
-        None => Addr::unchecked("none"),
-    };
+pub fn update_fees(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    founder_fee_bps: u16,
+    penalty_fee_bps: Option<u16>,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::FeeManager)?;
+    let fees = save_fees(deps.storage, founder_fee_bps, penalty_fee_bps)?;
 
-    if borrower != info.sender.clone() {
-        return Err(ContractError::NotBorrower { borrower: borrower });
-    }
-    let value = must_pay(&info, "inj")?.u128();
+    Ok(Response::new()
+        .add_event(action_event("update_fees")
+            .add_attribute("founder_fee_bps", fees.founder_fee_bps.to_string())
+            .add_attribute("penalty_fee_bps", fees.penalty_fee_bps.to_string())))
+}
 
-    if Uint128::from(value) < (curr_offer.amount + curr_offer.interest) {
-        return Err(ContractError::NotEnoughINJ {
-            amount: curr_offer.amount + curr_offer.interest
-        });
+fn save_fees(
+    storage: &mut dyn Storage,
+    founder_fee_bps: u16,
+    penalty_fee_bps: Option<u16>,
+) -> Result<FeeConfig, ContractError> {
+    // both founders are paid from the deposit, and from penalties
+    if 2 * u128::from(founder_fee_bps) > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidFee { founder_fee_bps });
     }
-
-    if curr_offer.status != Status::Taken {
-        return Err(ContractError::NotTaken {});
+    let mut fees = FEES.may_load(storage)?.unwrap_or_default();
+    if let Some(penalty_fee_bps) = penalty_fee_bps {
+        if 2 * u128::from(penalty_fee_bps) > BPS_DENOMINATOR {
+            return Err(ContractError::InvalidPenaltyFee { penalty_fee_bps });
+        }
+        fees.penalty_fee_bps = penalty_fee_bps;
     }
+    fees.founder_fee_bps = founder_fee_bps;
+    FEES.save(storage, &fees)?;
+    Ok(fees)
+}
 
-    if env.block.time >= curr_offer.active_till {
-        return Err(ContractError::LoanExpired {
-            active_till: curr_offer.active_till
-        });
+pub fn set_paused(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    operations: Option<Vec<Operation>>,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Guardian)?;
+    apply_pause(deps.storage, operations, paused)
+}
+
+fn apply_pause(
+    storage: &mut dyn Storage,
+    operations: Option<Vec<Operation>>,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let mut state = PAUSE.may_load(storage)?.unwrap_or_default();
+    let operations = operations.unwrap_or_else(|| Operation::ALL.to_vec());
+    state.paused.retain(|op| !operations.contains(op));
+    if paused {
+        state.paused.extend(operations.iter().copied());
     }
+    PAUSE.save(storage, &state)?;
 
-    let transfer_msg = BankMsg::Send {
-        to_address: curr_offer.lender.clone().into(),
-        amount: coins((curr_offer.amount + curr_offer.interest).into(), "inj"),
-    };
+    let names: Vec<String> = operations.iter().map(|op| op.to_string()).collect();
+    Ok(Response::new()
+        .add_event(action_event(if paused { "pause" } else { "unpause" })
+            .add_attribute("operations", names.join(","))))
+}
 
-    let recipient_string = match curr_offer.borrower.clone() {
-        Some(addr) => addr.clone().to_string(),
-        None => "None".to_string(),
-    };
-    let token_string = match curr_offer.token_id {
-        Some(t) => t.to_string(),
-        None => "None".to_string(),
-    };
-    let transfer: Cw721ExecuteMsg::<Empty, Empty> = Cw721ExecuteMsg::<Empty, Empty>::TransferNft {
-        recipient: recipient_string,
-        token_id: token_string,
-    };
-    let wasm_msg = WasmMsg::Execute {
-        contract_addr: collection.addr.to_string(),
-        msg: to_json_binary(&transfer)?,
-        funds: Vec::new(),
-    };
+pub fn set_emergency(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    ensure_role(deps.as_ref(), &info.sender, Role::Guardian)?;
+    apply_emergency(deps.storage, enabled)
+}
 
-    let updated_offer = Offer {
-        token_id: curr_offer.token_id,
-        offer_id: curr_offer.offer_id,
-        amount: curr_offer.amount,
-        active_till: curr_offer.active_till,
-        lender: curr_offer.lender.clone(),
-        interest: curr_offer.interest,
-        status: Status::Paid,
-        borrower: curr_offer.borrower.clone(),
-        duration: curr_offer.duration,
-    };
+fn apply_emergency(storage: &mut dyn Storage, enabled: bool) -> Result<Response, ContractError> {
+    let mut state = PAUSE.may_load(storage)?.unwrap_or_default();
+    state.emergency = enabled;
+    PAUSE.save(storage, &state)?;
 
-    offers[offer_id.u128() as usize] = updated_offer;
-    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
-    
-    Ok(Response::new().add_message(transfer_msg).add_message(wasm_msg))
+    Ok(Response::new()
+        .add_event(action_event("set_emergency")
+            .add_attribute("enabled", enabled.to_string())))
 }
 
-pub fn claim(
+fn ensure_not_paused(deps: Deps, operation: Operation) -> Result<(), ContractError> {
+    let state = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    if !state.allows(operation) {
+        return Err(ContractError::Paused { operation });
+    }
+    Ok(())
+}
+
+pub fn lend(
     deps: DepsMut, 
     env: Env, 
     info: MessageInfo, 
     collection_id: Uint128, 
-    offer_id: Uint128, 
+    duration: Uint128, 
+    auto_relend: Option<AutoRelend>,
 ) -> Result<Response, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
-
-    if collections.get(collection_id.u128() as usize).is_none() {
-        return Err(ContractError::InvalidCollectionId {collection_id});
-    }
-
-    let collection = &collections[collection_id.u128() as usize];
-    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
-
-    let curr_offer = &offers[offer_id.u128() as usize];
+    ensure_not_paused(deps.as_ref(), Operation::Lend)?;
 
-    if curr_offer.lender != info.sender {
-        return Err(ContractError::NotLender {});
-    }
+    let collection = load_collection(deps.as_ref(), collection_id)?;
 
-    if curr_offer.status != Status::Taken {
-        return Err(ContractError::NotPaid {});
+    if duration.is_zero() {
+        return Err(ContractError::InvalidDuration { duration });
     }
+    ensure_allowed(deps.as_ref(), collection_id, &collection, &info.sender)?;
 
-    if env.block.time < curr_offer.active_till {
-        return Err(ContractError::LoanActive {
-            active_till: curr_offer.active_till
-        });
-    }
+    let value = must_pay(&info, "inj")?.u128();
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let founders = FOUNDERS.load(deps.storage)?;
+    let fees = FEES.may_load(deps.storage)?.unwrap_or_default();
 
-    let recipient_string = curr_offer.lender.to_string();
-    let token_string = match curr_offer.token_id {
-        Some(t) => t.to_string(),
-        None => "None".to_string(),
-    };
-    let transfer: Cw721ExecuteMsg::<Empty, Empty> = Cw721ExecuteMsg::<Empty, Empty>::TransferNft {
-        recipient: recipient_string,
-        token_id: token_string,
+    // each founder takes a cut of the deposit
+    let commission = Uint128::from(value).multiply_ratio(fees.founder_fee_bps, BPS_DENOMINATOR).u128();
+    let cap = offer_cap(deps.as_ref(), &collection)?;
+    let amount = Uint128::from(value - (2*commission));
+    check_ltv(amount, cap)?;
+    let fee_msgs: Vec<BankMsg> = if commission == 0 {
+        vec![]
+    } else {
+        founders
+            .iter()
+            .map(|founder| BankMsg::Send {
+                to_address: founder.to_string(),
+                amount: coins(commission, "inj"),
+            })
+            .collect()
     };
-    let wasm_msg = WasmMsg::Execute {
-        contract_addr: collection.addr.to_string(),
-        msg: to_json_binary(&transfer)?,
-        funds: Vec::new(),
-    };
-
-    let updated_offer = Offer {
-        token_id: curr_offer.token_id,
-        offer_id: curr_offer.offer_id,
-        amount: curr_offer.amount,
-        active_till: curr_offer.active_till,
-        lender: curr_offer.lender.clone(),
-        interest: curr_offer.interest,
-        status: Status::Failed,
-        borrower: curr_offer.borrower.clone(),
-        duration: curr_offer.duration,
+    let new_offer = Offer {
+        offer_id: Uint128::from(offers.len() as u128),
+        amount,
+        active_till: Timestamp::from_nanos(0),
+        lender: info.sender.clone(),
+        status: Status::Open,
+        borrower: None,
+        // charged on the net amount the borrower receives, like updated and relent offers
+        interest: interest(collection.interest_mode, bps_rate(collection.apy_bps), duration, amount)?,
+        apy_bps: collection.apy_bps,
+        token_id: None,
+        token_amount: None,
+        bundle: vec![],
+        syndicate: vec![],
+        schedule: vec![],
+        late_penalty: None,
+        auto_relend: auto_relend.filter(|terms| terms.cycles > 0),
+        duration,
+        history: vec![Transition { status: Status::Open, at: env.block.time }],
     };
-
-    offers[offer_id.u128() as usize] = updated_offer;
+    index_open(deps.storage, collection_id, &new_offer)?;
+    offers.push(new_offer.clone());
     OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
-    
-    Ok(Response::new().add_message(wasm_msg))
+
+    Ok(Response::new()
+        .add_messages(fee_msgs)
+        .add_event(offer_event("lend", collection_id, &new_offer, Uint128::from(2*commission)))
+        .set_data(to_json_binary(&LendResponse { offer_id: new_offer.offer_id })?))
 }
 
-pub fn withdraw(
+#[allow(clippy::too_many_arguments)]
+pub fn update_offer(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    collection_id: Uint128, 
-    offer_id: Uint128, 
+    collection_id: Uint128,
+    offer_id: Uint128,
+    new_amount: Option<Uint128>,
+    new_duration: Option<Uint128>,
+    new_interest: Option<u32>,
 ) -> Result<Response, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
-
-    if collections.get(collection_id.u128() as usize).is_none() {
-        return Err(ContractError::InvalidCollectionId {collection_id});
-    }
-
-    let collection = &collections[collection_id.u128() as usize];
+    let collection = load_collection(deps.as_ref(), collection_id)?;
     let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let index = offer_index(&offers, offer_id)?;
+    let offer = &mut offers[index];
 
-
-    // let best_offer: Uint128 = get_best_offer(deps, collection_id)?;
-    let curr_offer = &offers[offer_id.u128() as usize];
-
-    if curr_offer.lender != info.sender {
+    if offer.lender != info.sender {
         return Err(ContractError::NotLender {});
     }
-
-    if curr_offer.status != Status::Open {
+    if offer.status != Status::Open {
         return Err(ContractError::NotOpen {});
     }
 
-    let transfer_msg = BankMsg::Send {
-        to_address: curr_offer.lender.clone().into(),
-        amount: coins(curr_offer.amount.into(), "inj"),
-    };
+    let amount = new_amount.unwrap_or(offer.amount);
+    let mut msgs: Vec<BankMsg> = vec![];
+    let mut commission = Uint128::zero();
+    if amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    if amount > offer.amount {
+        // a top up is a new deposit and pays the founders like one
+        ensure_not_paused(deps.as_ref(), Operation::Lend)?;
+        ensure_allowed(deps.as_ref(), collection_id, &collection, &info.sender)?;
+        let fees = FEES.may_load(deps.storage)?.unwrap_or_default();
+        let top_up = amount - offer.amount;
+        commission = top_up.multiply_ratio(fees.founder_fee_bps, BPS_DENOMINATOR);
+        let required = top_up
+            .checked_add(commission + commission)
+            .map_err(|_| ContractError::InterestOverflow {})?;
+        let value = must_pay(&info, "inj")?;
+        if value < required {
+            return Err(ContractError::NotEnoughINJ { amount: required });
+        }
+        if !commission.is_zero() {
+            for founder in FOUNDERS.load(deps.storage)? {
+                msgs.push(BankMsg::Send { to_address: founder.to_string(), amount: coins(commission.u128(), "inj") });
+            }
+        }
+        if value > required {
+            msgs.push(BankMsg::Send { to_address: info.sender.to_string(), amount: coins((value - required).u128(), "inj") });
+        }
+    } else {
+        nonpayable(&info)?;
+        if amount < offer.amount {
+            ensure_not_paused(deps.as_ref(), Operation::Withdraw)?;
+            msgs.push(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: coins((offer.amount - amount).u128(), "inj"),
+            });
+        }
+    }
+    let cap = offer_cap(deps.as_ref(), &collection)?;
+    check_ltv(amount, cap)?;
 
-    let updated_offer = Offer {
-        token_id: curr_offer.token_id,
-        offer_id: curr_offer.offer_id,
-        amount: curr_offer.amount,
-        active_till: curr_offer.active_till,
-        lender: curr_offer.lender.clone(),
-        interest: curr_offer.interest,
-        status: Status::Cancelled,
-        borrower: curr_offer.borrower.clone(),
-        duration: curr_offer.duration,
-    };
+    let duration = new_duration.unwrap_or(offer.duration);
+    if duration.is_zero() {
+        return Err(ContractError::InvalidDuration { duration });
+    }
+    if new_interest.is_some() || new_duration.is_some() {
+        // a new price is a new lending decision
+        ensure_not_paused(deps.as_ref(), Operation::Lend)?;
+    }
+    if let Some(apy_bps) = new_interest {
+        // zero marks offers from before rates were stored
+        if apy_bps == 0 || apy_bps > MAX_APY_BPS {
+            return Err(ContractError::InvalidApy { apy_bps, max: MAX_APY_BPS });
+        }
+        offer.apy_bps = apy_bps;
+    }
+    offer.amount = amount;
+    offer.duration = duration;
+    offer.interest = interest(collection.interest_mode, bps_rate(offer.apy_bps), duration, amount)?;
 
-    offers[offer_id.u128() as usize] = updated_offer;
+    let event = offer_event("update_offer", collection_id, offer, commission + commission);
+    let data = UpdateOfferResponse {
+        offer_id,
+        amount,
+        interest: offer.interest,
+        duration,
+    };
     OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
-    
-    Ok(Response::new().add_message(transfer_msg))
 
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_event(event)
+        .set_data(to_json_binary(&data)?))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
-    match msg {
-        QueryMsg::GetBestOffer { collection_id, from } => Ok(to_json_binary(&get_best_offer(deps, collection_id, from)?)?),
-        QueryMsg::CollectionsList {} => Ok(to_json_binary(&get_collections_list(deps)?)?),
-        QueryMsg::GetOffers { collection_id, from } => Ok(to_json_binary(&get_offers(deps, collection_id, from)?)?),
-        QueryMsg::GetCollectionData { collection_id, from } => Ok(to_json_binary(&get_collections_data(deps, collection_id, from)?)?),
+pub fn stop_auto_relend(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    offer_id: Uint128,
+) -> Result<Response, ContractError> {
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let index = offer_index(&offers, offer_id)?;
+    if offers[index].lender != info.sender {
+        return Err(ContractError::NotLender {});
     }
+    offers[index].auto_relend = None;
+    OFFERS.save(deps.storage, collection.addr, &offers)?;
+
+    Ok(Response::new()
+        .add_event(offer_event("stop_auto_relend", collection_id, &offers[index], Uint128::zero())))
 }
 
-fn get_best_offer(deps: Deps, collection_id: Uint128, from: Addr) -> Result<Uint128, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
-    let offers = OFFERS.load(deps.storage, collections[collection_id.u128() as usize].addr.clone())?;
-    let mut best_int: i32 = -1;
-    let mut best_offer: Option<Offer> = None;
-    let mut done = false;
-    for i in 0..offers.len() {
-        if offers[i].status == Status::Open {
-            if offers[i].lender.clone() != from.clone() {
-                if !done {
-                    best_int = i as i32;
-                    best_offer = Some(offers[i].clone());
-                    done = true;
-                }
-                if offers[i].amount > best_offer.clone().unwrap().amount {
-                    best_offer = Some(offers[i].clone());
-                    best_int = i as i32;
-                } else if (offers[i].amount == best_offer.clone().unwrap().amount) && (offers[i].interest < best_offer.clone().unwrap().interest) {
-                    best_offer = Some(offers[i].clone());
-                    best_int = i as i32;
-                } else if (offers[i].amount == best_offer.clone().unwrap().amount) && (offers[i].interest == best_offer.clone().unwrap().interest) && (offers[i].duration == best_offer.clone().unwrap().duration) {
-                    best_offer = Some(offers[i].clone());
-                    best_int = i as i32;
-                }
-            }
-        }
+/// New open offer lending out `amount` repaid to `offer` on the same terms, if
+/// the lender opted in and could still lend it now.
+fn relend_offer(
+    deps: Deps,
+    env: &Env,
+    collection_id: Uint128,
+    collection: &Collection,
+    offer: &Offer,
+    offer_id: Uint128,
+    amount: Uint128,
+) -> Result<Option<Offer>, ContractError> {
+    let Some(terms) = &offer.auto_relend else {
+        return Ok(None);
+    };
+    if terms.cycles == 0
+        || amount.is_zero()
+        || ensure_not_paused(deps, Operation::Lend).is_err()
+        || ensure_allowed(deps, collection_id, collection, &offer.lender).is_err()
+    {
+        return Ok(None);
     }
-    if best_int < 0 {
-        return Err(ContractError::NoOffer {});
+    let cap = offer_cap(deps, collection)?;
+    if check_ltv(amount, cap).is_err() {
+        return Ok(None);
     }
-    Ok(Uint128::from(best_int as u64))
+    let Ok(interest) = interest(collection.interest_mode, bps_rate(offer.apy_bps), offer.duration, amount) else {
+        return Ok(None);
+    };
+    let cycles = terms.cycles - 1;
+    Ok(Some(Offer {
+        offer_id,
+        amount,
+        active_till: Timestamp::from_nanos(0),
+        interest,
+        apy_bps: offer.apy_bps,
+        lender: offer.lender.clone(),
+        borrower: None,
+        token_id: None,
+        token_amount: None,
+        bundle: vec![],
+        syndicate: vec![],
+        schedule: vec![],
+        late_penalty: None,
+        auto_relend: (cycles > 0).then_some(AutoRelend { cycles, with_interest: terms.with_interest }),
+        status: Status::Open,
+        duration: offer.duration,
+        history: vec![Transition { status: Status::Open, at: env.block.time }],
+    }))
 }
 
-fn get_collections_list(deps: Deps) -> Result<Vec<Collection>, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
-    Ok(collections)
+/// Time at which a loan of `duration` seconds taken at `start` matures.
+fn maturity(start: Timestamp, duration: Uint128) -> Result<Timestamp, ContractError> {
+    u64::try_from(duration.u128())
+        .ok()
+        .and_then(|secs| secs.checked_mul(1_000_000_000))
+        .and_then(|nanos| start.nanos().checked_add(nanos))
+        .map(Timestamp::from_nanos)
+        .ok_or(ContractError::InvalidDuration { duration })
 }
 
-fn get_offers(deps: Deps, collection_id: Uint128, from: Addr) -> Result<OfferData, ContractError> {
+fn load_collection(deps: Deps, collection_id: Uint128) -> Result<Collection, ContractError> {
     let collections = COLLECTIONS.load(deps.storage)?;
-    let offers = OFFERS.load(deps.storage, collections[collection_id.u128() as usize].addr.clone())?;
-    let borrowed: Vec<Offer> = offers
-        .iter()
-        .filter(|&x| x.borrower.clone() == Some(from.clone()))
-        .cloned()
-        .collect();
-    let lent: Vec<Offer> = offers
-        .iter()
-        .filter(|&x| x.lender.clone() == from.clone())
+    usize::try_from(collection_id.u128())
+        .ok()
+        .and_then(|i| collections.get(i))
         .cloned()
-        .collect();
-    let offer_data = OfferData {
-        lent: lent,
-        borrowed: borrowed
-    };
-    Ok(offer_data)
+        .ok_or(ContractError::InvalidCollectionId { collection_id })
 }
 
-fn get_collections_data(deps: Deps, collection_id: Uint128, from: Addr) -> Result<CollectionData, ContractError> {
-    let collections = COLLECTIONS.load(deps.storage)?;
-    let offers = OFFERS.load(deps.storage, collections[collection_id.u128() as usize].addr.clone())?;
-    let best_offer: Option<Offer> = match get_best_offer(deps, collection_id, from.clone()) {
-        Ok(best_offer) => Some(offers[best_offer.u128() as usize].clone()),
-        Err(_) => None,
-    };
-    let mut taken_offers = 0;
-    let mut total_pool: u128 = 0;
-    for i in 0..offers.len() {
-        if offers[i].status != Status::Open {
-            taken_offers+=1;
-        } else {
-            total_pool+=offers[i].amount.u128();
+fn offer_index(offers: &[Offer], offer_id: Uint128) -> Result<usize, ContractError> {
+    usize::try_from(offer_id.u128())
+        .ok()
+        .filter(|i| *i < offers.len())
+        .ok_or(ContractError::InvalidOfferId { offer_id })
+}
+
+pub fn borrow(
+    deps: DepsMut, 
+    env: Env, 
+    info: MessageInfo, 
+    collection_id: Uint128, 
+    token_id: String, 
+) -> Result<Response, ContractError> {
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    if collection.standard != TokenStandard::Cw721 {
+        return Err(ContractError::WrongStandard { standard: collection.standard });
+    }
+    let wasm_msg = transfer_cw721(&collection.addr, &token_id, &env.contract.address)?;
+    let pledge = Pledge { token_id, token_amount: None, bundle: vec![] };
+    let response = take_best_offer(deps, env, info.sender, collection_id, collection, pledge, None)?;
+    Ok(response.add_message(wasm_msg))
+}
+
+pub fn borrow_installments(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    token_id: String,
+    installments: u32,
+) -> Result<Response, ContractError> {
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    if collection.standard != TokenStandard::Cw721 {
+        return Err(ContractError::WrongStandard { standard: collection.standard });
+    }
+    let wasm_msg = transfer_cw721(&collection.addr, &token_id, &env.contract.address)?;
+    let pledge = Pledge { token_id, token_amount: None, bundle: vec![] };
+    let response = take_best_offer(deps, env, info.sender, collection_id, collection, pledge, Some(installments))?;
+    Ok(response.add_message(wasm_msg))
+}
+
+pub fn borrow_bundle(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    tokens: Vec<BundleToken>,
+) -> Result<Response, ContractError> {
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let primary = tokens
+        .iter()
+        .position(|t| t.collection_id == collection_id)
+        .ok_or(ContractError::InvalidBundle {})?;
+
+    let mut items: Vec<(Uint128, BundleItem)> = Vec::with_capacity(tokens.len());
+    for token in tokens.iter() {
+        let item_collection = load_collection(deps.as_ref(), token.collection_id)?;
+        if item_collection.standard != TokenStandard::Cw721 {
+            return Err(ContractError::WrongStandard { standard: item_collection.standard });
+        }
+        if items.iter().any(|(id, item)| *id == token.collection_id && item.token_id == token.token_id) {
+            return Err(ContractError::InvalidBundle {});
         }
+        // the lending collection is checked with the offer
+        if token.collection_id != collection_id {
+            ensure_allowed(deps.as_ref(), token.collection_id, &item_collection, &info.sender)?;
+        }
+        ensure_not_blocked(deps.as_ref(), token.collection_id, &token.token_id)?;
+        items.push((token.collection_id, BundleItem { collection: item_collection.addr, token_id: token.token_id.clone() }));
     }
-    let collection_data = CollectionData {
-        collection_id: collection_id.clone(),
-        total_offers: Uint128::from(offers.len() as u64),
-        offers_taken: Uint128::from(taken_offers as u64),
-        best_offer: best_offer,
-        total_pool: Uint128::from(total_pool as u128)
+
+    let wasm_msgs = items
+        .iter()
+        .map(|(_, item)| transfer_cw721(&item.collection, &item.token_id, &env.contract.address))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (_, primary) = items.remove(primary);
+    let pledge = Pledge {
+        token_id: primary.token_id,
+        token_amount: None,
+        bundle: items.into_iter().map(|(_, item)| item).collect(),
     };
-    Ok(collection_data)
+    let response = take_best_offer(deps, env, info.sender, collection_id, collection, pledge, None)?;
+    Ok(response.add_messages(wasm_msgs))
 }
 
+pub fn borrow_aggregated(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    token_id: String,
+    fill: Fill,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Borrow)?;
 
-#[cfg(test)]
-mod tests {
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{from_json};
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    if collection.standard != TokenStandard::Cw721 {
+        return Err(ContractError::WrongStandard { standard: collection.standard });
+    }
+    ensure_allowed(deps.as_ref(), collection_id, &collection, &info.sender)?;
+    ensure_not_blocked(deps.as_ref(), collection_id, &token_id)?;
 
-    use super::*;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let cap = loan_cap(deps.as_ref(), &collection.addr, &collection.valuation)?;
+    let indices = fill_offers(&offers, &info.sender, fill, cap)?;
 
-    #[test]
-    fn test () {
-        let mut deps = mock_dependencies();
+    // the loan runs until the shortest of the filled offers ends
+    let duration = indices.iter().map(|&i| offers[i].duration).min().unwrap_or_default();
+    let active_till = maturity(env.block.time, duration)?;
+    let syndicate: Vec<Uint128> = indices.iter().map(|&i| offers[i].offer_id).collect();
 
-        // instantiate an empty contract
-        let instantiate_msg = InstantiateMsg {
-            founder_one: Addr::unchecked(&String::from("foun1")),
-            founder_two: Addr::unchecked(&String::from("foun1"))
-        };
-        let info = mock_info(&String::from("anyone"), &[]);
-        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+    let mut amount = Uint128::zero();
+    let mut interest = Uint128::zero();
+    let mut events = Vec::with_capacity(indices.len());
+    for &i in indices.iter() {
+        let offer = &mut offers[i];
+        // interest only runs for the shared term, not the offer's own
+        offer.interest = crate::interest::interest(collection.interest_mode, bps_rate(offer.apy_bps), duration, offer.amount)?;
+        offer.duration = duration;
+        offer.active_till = active_till;
+        offer.borrower = Some(info.sender.clone());
+        offer.token_id = Some(token_id.clone());
+        offer.syndicate = syndicate.clone();
+        offer.late_penalty = collection.late_penalty.clone();
+        unindex_open(deps.storage, collection_id, offer);
+        transition(offer, Status::Taken, env.block.time)?;
+        index_maturity(deps.storage, collection_id, offer)?;
+        record_taken(deps.storage, collection_id, offer)?;
+        amount = amount.checked_add(offer.amount).map_err(|_| ContractError::InterestOverflow {})?;
+        interest = interest.checked_add(offer.interest).map_err(|_| ContractError::InterestOverflow {})?;
+        events.push(offer_event("borrow", collection_id, offer, Uint128::zero()));
+    }
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
 
-        let add = ExecuteMsg::AddCollection {
-            collection_address: Addr::unchecked(&String::from("coll_one")),
-            apy: Uint128::from(90 as u128)
-        };
+    let transfer_msg = BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: coins(amount.u128(), "inj"),
+    };
+    let wasm_msg = transfer_cw721(&collection.addr, &token_id, &env.contract.address)?;
+    let data = BorrowResponse {
+        offer_id: syndicate[0],
+        amount,
+        interest,
+        due: active_till,
+    };
 
-        let info = mock_info(&String::from("anyone"), &[]);
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_message(wasm_msg)
+        .add_events(events)
+        .set_data(to_json_binary(&data)?))
+}
 
-        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
-        assert_eq!(0, res.messages.len());
+/// Indices of the offers `borrower` fills, senior first. Their total must stay
+/// within `cap`.
+fn fill_offers(
+    offers: &[Offer],
+    borrower: &Addr,
+    fill: Fill,
+    cap: Option<Uint128>,
+) -> Result<Vec<usize>, ContractError> {
+    let indices = match fill {
+        Fill::OfferIds(offer_ids) => {
+            let mut indices: Vec<usize> = Vec::with_capacity(offer_ids.len());
+            for offer_id in offer_ids {
+                let i = offer_index(offers, offer_id)?;
+                if indices.contains(&i) {
+                    return Err(ContractError::InvalidOfferId { offer_id });
+                }
+                if offers[i].status != Status::Open {
+                    return Err(ContractError::NotOpen {});
+                }
+                if offers[i].lender == *borrower {
+                    return Err(ContractError::IsLender {});
+                }
+                indices.push(i);
+            }
+            indices
+        },
+        Fill::TargetAmount(target) => {
+            // largest offers first, then the cheapest
+            let mut open: Vec<usize> = (0..offers.len())
+                .filter(|&i| offers[i].status == Status::Open && offers[i].lender != *borrower)
+                .collect();
+            open.sort_by(|&a, &b| offers[b].amount.cmp(&offers[a].amount).then(offers[a].interest.cmp(&offers[b].interest)));
+            let mut indices = vec![];
+            let mut filled = Uint128::zero();
+            for i in open {
+                if filled >= target {
+                    break;
+                }
+                let next = filled.saturating_add(offers[i].amount);
+                if check_ltv(next, cap).is_ok() {
+                    filled = next;
+                    indices.push(i);
+                }
+            }
+            if filled < target {
+                return Err(ContractError::TargetNotFilled { amount: filled });
+            }
+            indices
+        },
+    };
+    if indices.is_empty() {
+        return Err(ContractError::NoOffer {});
+    }
 
-        let query_msg = QueryMsg::CollectionsList {};
-        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        let collections: Vec<Collection> = from_json(res).unwrap();
-        println!("{:?}", collections);
+    let total = indices
+        .iter()
+        .try_fold(Uint128::zero(), |sum, &i| sum.checked_add(offers[i].amount))
+        .map_err(|_| ContractError::InterestOverflow {})?;
+    check_ltv(total, cap)?;
+    Ok(indices)
+}
 
-        // assert_eq!(Addr::unchecked(&String::from("coll_one")), collections[0]);
+/// Offers settled together with the one at `index`.
+fn loan_members(offers: &[Offer], index: usize) -> Result<Vec<usize>, ContractError> {
+    if offers[index].syndicate.is_empty() {
+        return Ok(vec![index]);
+    }
+    offers[index]
+        .syndicate
+        .iter()
+        .map(|&offer_id| offer_index(offers, offer_id))
+        .collect()
+}
 
-        let add = ExecuteMsg::Lend {
-            collection_id: Uint128::from(0 as u64), 
-            duration: Uint128::from(86400 as u64), 
-        };
+/// Borrows against cw1155 tokens the collection has already moved into escrow.
+pub fn receive_cw1155(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw1155ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let Cw1155HookMsg::Borrow { collection_id } = from_json(&msg.msg)?;
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    // only the collection itself can vouch for the transfer
+    if collection.addr != info.sender {
+        return Err(ContractError::NotCollection { collection: collection.addr });
+    }
+    if collection.standard != TokenStandard::Cw1155 {
+        return Err(ContractError::WrongStandard { standard: collection.standard });
+    }
+    if msg.amount.is_zero() {
+        return Err(ContractError::NotEnoughTokens {});
+    }
+    let borrower = match msg.from {
+        Some(from) => deps.api.addr_validate(&from)?,
+        None => return Err(ContractError::NoCollateralOwner {}),
+    };
+    let pledge = Pledge { token_id: msg.token_id, token_amount: Some(msg.amount), bundle: vec![] };
+    take_best_offer(deps, env, borrower, collection_id, collection, pledge, None)
+}
 
-        let info = mock_info(&String::from("caller"), &coins(
-            10000000000000000000, "inj"));
+/// Collateral posted for a loan.
+struct Pledge {
+    token_id: String,
+    token_amount: Option<Uint128>,
+    bundle: Vec<BundleItem>,
+}
 
-        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
-        println!("{:?}", res.messages);
-        let base_amount = (10000000000000) as u64;
-        let percentage: u64 = 25;
-        let divisor: u64 = 1000;
+/// Lends the best open offer to `borrower` against the given collateral, as a
+/// bullet loan or split into `installments`.
+fn take_best_offer(
+    deps: DepsMut,
+    env: Env,
+    borrower: Addr,
+    collection_id: Uint128,
+    collection: Collection,
+    pledge: Pledge,
+    installments: Option<u32>,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Borrow)?;
 
-        // Perform the multiplication and division
-        let commission = (base_amount as u64) * percentage / divisor;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
 
-        println!("{}", Uint128::from(commission));
-        assert_eq!(2, res.messages.len());
+    ensure_allowed(deps.as_ref(), collection_id, &collection, &borrower)?;
+    ensure_not_blocked(deps.as_ref(), collection_id, &pledge.token_id)?;
 
-        let add = ExecuteMsg::Lend {
-            collection_id: Uint128::from(0 as u64), 
-            duration: Uint128::from(86400 as u64), 
-        };
+    // only offers within the current collateral limit are considered
+    let cap = pledge_cap(deps.as_ref(), &collection, &pledge)?;
+    let index = select_offer(&offers, cap, &borrower)?;
+    let curr_offer = &offers[index];
 
-        let info = mock_info(&String::from("caller"), &coins(10000, "inj"));
+    if curr_offer.lender == borrower {
+        return Err(ContractError::IsLender {});
+    }
 
-        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
-        assert_eq!(2, res.messages.len());
+    let transfer_msg = BankMsg::Send {
+        to_address: borrower.to_string(),
+        amount: coins(curr_offer.amount.u128(), "inj"),
+    };
 
-        let query_msg = QueryMsg::GetBestOffer { collection_id: Uint128::from(0 as u64), from: Addr::unchecked(&String::from("borower")) };
-        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        let best: Uint128 = from_json(res).unwrap();
-        assert_eq!(Uint128::from(0 as u64), best);
+    let mut updated_offer = curr_offer.clone();
+    updated_offer.active_till = maturity(env.block.time, curr_offer.duration)?;
+    updated_offer.borrower = Some(borrower);
+    updated_offer.token_id = Some(pledge.token_id);
+    updated_offer.token_amount = pledge.token_amount;
+    updated_offer.bundle = pledge.bundle;
+    updated_offer.late_penalty = collection.late_penalty.clone();
+    if let Some(installments) = installments {
+        updated_offer.schedule = amortize(
+            collection.interest_mode,
+            updated_offer.apy_bps,
+            updated_offer.amount,
+            env.block.time,
+            updated_offer.duration,
+            installments,
+        )?;
+        // interest owed on the declining balance replaces the bullet interest
+        updated_offer.interest = updated_offer
+            .schedule
+            .iter()
+            .try_fold(Uint128::zero(), |sum, i| sum.checked_add(i.interest))
+            .map_err(|_| ContractError::InterestOverflow {})?;
+    }
+    unindex_open(deps.storage, collection_id, &updated_offer);
+    transition(&mut updated_offer, Status::Taken, env.block.time)?;
 
-        let query_msg = QueryMsg::GetCollectionData { collection_id: Uint128::from(0 as u64), from: Addr::unchecked(&String::from("borrower")) };
-        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        let best: CollectionData = from_json(res).unwrap();
-        // assert_eq!(best, CollectionData {
-        //     collection_id: Uint128::from(0 as u64),
-        //     total_offers: Uint128::from(2 as u64),
-        //     offers_taken: Uint128::from(0 as u64),
-        //     best_offer: None,
-        //     total_pool: Uint128::from(10100 as u128)
-        // });
+    let event = offer_event("borrow", collection_id, &updated_offer, Uint128::zero());
+    let data = BorrowResponse {
+        offer_id: updated_offer.offer_id,
+        amount: updated_offer.amount,
+        interest: updated_offer.interest,
+        due: updated_offer.active_till,
+    };
+    index_maturity(deps.storage, collection_id, &updated_offer)?;
+    record_taken(deps.storage, collection_id, &updated_offer)?;
+    offers[index] = updated_offer;
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_event(event)
+        .set_data(to_json_binary(&data)?))
 
-        let borrow = ExecuteMsg::Borrow {
-            collection_id: Uint128::from(0 as u64), 
-            token_id: Uint128::from(0 as u64),
-        };
+}
 
-        let info = mock_info(&String::from("borrower"), &[]);
+pub fn repay(
+    deps: DepsMut, 
+    env: Env, 
+    info: MessageInfo, 
+    collection_id: Uint128, 
+    offer_id: Uint128, 
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Repay)?;
 
-        let res = execute(deps.as_mut(), mock_env(), info, borrow).unwrap();
-        assert_eq!(2, res.messages.len());
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
 
-        // let query_msg = QueryMsg::GetCollectionData { collection_id: Uint128::from(0 as u64), from: Addr::unchecked(&String::from("borower")) };
-        // let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        // let best: CollectionData = from_json(res).unwrap();
-        // assert_eq!(best, CollectionData {
-        //     collection_id: Uint128::from(1 as u64),
-        //     total_offers: Uint128::from(1 as u64),
-        //     offers_taken: Uint128::from(1 as u64),
-        //     best_offer: None,
-        //     total_pool: Uint128::from(1 as u128)
-        // });
-
-        let query_msg = QueryMsg::GetOffers { collection_id: Uint128::from(0 as u64), from:  Addr::unchecked(&String::from("borrower"))};
-        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        let offers: OfferData = from_json(res).unwrap();
-        let borrower = match offers.borrowed[0].borrower.clone() {
-            Some(addr) => addr.clone(),
-            None => Addr::unchecked("none"),
-        };
-        println!{"{:?}", offers};
-        assert_eq!(&borrower.to_string(), &String::from("borrower"));
 
-        let borrow = ExecuteMsg::Repay {
-            collection_id: Uint128::from(0 as u64), 
-            offer_id: Uint128::from(0 as u64),
+    let index = offer_index(&offers, offer_id)?;
+    let curr_offer = &offers[index];
+    let borrower = match curr_offer.borrower.clone() {
+        Some(addr) => addr.clone(),
+        None => Addr::unchecked("none"),
+    };
+
+    if borrower != info.sender {
+        return Err(ContractError::NotBorrower { borrower });
+    }
+    let value = must_pay(&info, "inj")?.u128();
+    // aggregated loans are repaid in full, each lender gets its own share.
+    // Installment loans settle with every installment still unpaid.
+    let members = loan_members(&offers, index)?;
+    let fees = FEES.may_load(deps.storage)?.unwrap_or_default();
+    let mut owed = Uint128::zero();
+    let mut penalty = Uint128::zero();
+    let mut protocol_cut = Uint128::zero();
+    let mut transfer_msgs = Vec::with_capacity(members.len());
+    let mut relent: Vec<Offer> = vec![];
+    for &i in members.iter() {
+        let late = penalty_owed(&offers[i], env.block.time)?;
+        let cut = late.multiply_ratio(fees.penalty_fee_bps, BPS_DENOMINATOR);
+        let share = offers[i].owed()
+            .and_then(|owed| owed.checked_add(late).ok())
+            .ok_or(ContractError::InterestOverflow {})?;
+        owed = owed.checked_add(share).map_err(|_| ContractError::InterestOverflow {})?;
+        penalty = penalty.checked_add(late).map_err(|_| ContractError::InterestOverflow {})?;
+        protocol_cut = protocol_cut.checked_add(cut).map_err(|_| ContractError::InterestOverflow {})?;
+
+        // opted in lenders keep their principal, and maybe interest, lent out
+        let mut payout = share - cut - cut;
+        let recycled = match &offers[i].auto_relend {
+            Some(terms) if terms.with_interest => offers[i].owed(),
+            _ => Some(offers[i].amount),
         };
+        let recycled = recycled.filter(|amount| offers[i].schedule.is_empty() && *amount <= payout);
+        if let Some(amount) = recycled {
+            let next_id = Uint128::from((offers.len() + relent.len()) as u128);
+            if let Some(offer) = relend_offer(deps.as_ref(), &env, collection_id, &collection, &offers[i], next_id, amount)? {
+                payout -= offer.amount;
+                relent.push(offer);
+            }
+        }
+        if !payout.is_zero() {
+            transfer_msgs.push(BankMsg::Send {
+                to_address: offers[i].lender.to_string(),
+                amount: coins(payout.u128(), "inj"),
+            });
+        }
+    }
+    transfer_msgs.extend(penalty_fee_msgs(deps.storage, protocol_cut)?);
 
-        let info = mock_info(&String::from("borrower"), &coins(9817600508718498930, "inj"));
-        assert_eq!(borrower, info.sender.clone());
+    if Uint128::from(value) < owed {
+        return Err(ContractError::NotEnoughINJ {
+            amount: owed
+        });
+    }
 
-        let res = execute(deps.as_mut(), mock_env(), info, borrow).unwrap();
-        assert_eq!(2, res.messages.len());
+    if curr_offer.status != Status::Taken {
+        return Err(ContractError::NotTaken {});
+    }
+
+    if env.block.time >= curr_offer.grace_end() {
+        return Err(ContractError::LoanExpired {
+            active_till: curr_offer.grace_end()
+        });
+    }
 
+    let wasm_msgs = release(&collection, curr_offer, &env.contract.address, &borrower)?;
 
-        let withdraw = ExecuteMsg::Withdraw {
-            collection_id: Uint128::from(0 as u64), 
-            offer_id: Uint128::from(1 as u64),
-        };
+    // the lenders are paid out in the same transaction, so the loan settles immediately
+    let mut events = Vec::with_capacity(members.len());
+    for &i in members.iter() {
+        let principal = offers[i].principal_owed();
+        let interest = offers[i].owed().ok_or(ContractError::InterestOverflow {})? - principal;
+        record_payment(deps.storage, collection_id, principal, interest)?;
+        record_repaid(deps.storage, collection_id)?;
+        offers[i].schedule.iter_mut().for_each(|installment| installment.paid = true);
+        transition(&mut offers[i], Status::Paid, env.block.time)?;
+        transition(&mut offers[i], Status::Closed, env.block.time)?;
+        unindex_maturity(deps.storage, collection_id, &offers[i]);
+        events.push(offer_event("repay", collection_id, &offers[i], Uint128::zero()));
+    }
+    for offer in relent {
+        index_open(deps.storage, collection_id, &offer)?;
+        events.push(offer_event("relend", collection_id, &offer, Uint128::zero()));
+        offers.push(offer);
+    }
+    let data = RepayResponse {
+        offer_id,
+        amount_paid: owed,
+        penalty,
+    };
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    
+    Ok(Response::new()
+        .add_messages(transfer_msgs)
+        .add_messages(wasm_msgs)
+        .add_events(events)
+        .set_data(to_json_binary(&data)?))
+}
+
+pub fn pay_installment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_id: Uint128,
+    offer_id: Uint128,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Repay)?;
 
-        let info = mock_info(&String::from("caller"), &[]);
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let index = offer_index(&offers, offer_id)?;
+    let offer = &mut offers[index];
 
-        let res = execute(deps.as_mut(), mock_env(), info, withdraw).unwrap();
-        assert_eq!(1, res.messages.len());
+    if offer.borrower.as_ref() != Some(&info.sender) {
+        return Err(ContractError::NotBorrower {
+            borrower: offer.borrower.clone().unwrap_or_else(|| Addr::unchecked("none")),
+        });
     }
-}
\ No newline at end of file
+    if offer.status != Status::Taken {
+        return Err(ContractError::NotTaken {});
+    }
+    let next = offer.next_installment().ok_or(ContractError::NoSchedule {})?;
+    if env.block.time >= offer.grace_end() {
+        return Err(ContractError::LoanExpired { active_till: offer.grace_end() });
+    }
+    let installment = &offer.schedule[next];
+    let penalty = match &offer.late_penalty {
+        Some(terms) => late_penalty(terms.apr_bps, installment.principal, installment.due, env.block.time)?,
+        None => Uint128::zero(),
+    };
+    let amount = installment.principal
+        .checked_add(installment.interest)
+        .and_then(|amount| amount.checked_add(penalty))
+        .map_err(|_| ContractError::InterestOverflow {})?;
+    if must_pay(&info, "inj")? < amount {
+        return Err(ContractError::NotEnoughINJ { amount });
+    }
+
+    let fees = FEES.may_load(deps.storage)?.unwrap_or_default();
+    let cut = penalty.multiply_ratio(fees.penalty_fee_bps, BPS_DENOMINATOR);
+    // the loan can next default once the following installment is overdue
+    GRACE_ENDS.remove(deps.storage, grace_key(collection_id, offer));
+    offer.schedule[next].paid = true;
+    record_payment(deps.storage, collection_id, offer.schedule[next].principal, offer.schedule[next].interest)?;
+    let mut response = Response::new()
+        .add_message(BankMsg::Send {
+            to_address: offer.lender.to_string(),
+            amount: coins((amount - cut - cut).u128(), "inj"),
+        })
+        .add_messages(penalty_fee_msgs(deps.storage, cut)?);
+    let settled = offer.next_installment().is_none();
+    if settled {
+        response = response.add_messages(release(&collection, offer, &env.contract.address, &info.sender)?);
+        transition(offer, Status::Paid, env.block.time)?;
+        transition(offer, Status::Closed, env.block.time)?;
+        unindex_maturity(deps.storage, collection_id, offer);
+        record_repaid(deps.storage, collection_id)?;
+    } else {
+        GRACE_ENDS.save(deps.storage, grace_key(collection_id, offer), &Empty {})?;
+    }
+    let event = offer_event("pay_installment", collection_id, offer, Uint128::zero())
+        .add_attribute("installment", next.to_string())
+        .add_attribute("amount_paid", amount)
+        .add_attribute("penalty", penalty);
+    let data = PayInstallmentResponse {
+        offer_id,
+        installment: next as u32,
+        amount_paid: amount,
+        penalty,
+        settled,
+    };
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+
+    Ok(response
+        .add_event(event)
+        .set_data(to_json_binary(&data)?))
+}
+
+/// Adds a loan that was just taken to `MATURITIES`, and to `GRACE_ENDS` while
+/// it can still default.
+fn index_maturity(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) -> Result<(), ContractError> {
+    let key = (offer.active_till.nanos(), collection_id.u128(), offer.offer_id.u128());
+    MATURITIES.save(storage, key, &Empty {})?;
+    if offer.status == Status::Taken {
+        GRACE_ENDS.save(storage, grace_key(collection_id, offer), &Empty {})?;
+    }
+    Ok(())
+}
+
+fn unindex_maturity(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) {
+    MATURITIES.remove(storage, (offer.active_till.nanos(), collection_id.u128(), offer.offer_id.u128()));
+    GRACE_ENDS.remove(storage, grace_key(collection_id, offer));
+}
+
+fn grace_key(collection_id: Uint128, offer: &Offer) -> (u64, u128, u128) {
+    (offer.grace_end().nanos(), collection_id.u128(), offer.offer_id.u128())
+}
+
+/// Adds an offer that was just listed to `OPEN_OFFERS`. Offers stored before
+/// history was recorded have no listing time and never expire.
+fn index_open(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) -> Result<(), ContractError> {
+    if let Some(listed) = offer.transitioned_at(&Status::Open) {
+        OPEN_OFFERS.save(storage, (listed.nanos(), collection_id.u128(), offer.offer_id.u128()), &Empty {})?;
+    }
+    Ok(())
+}
+
+fn unindex_open(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) {
+    if let Some(listed) = offer.transitioned_at(&Status::Open) {
+        OPEN_OFFERS.remove(storage, (listed.nanos(), collection_id.u128(), offer.offer_id.u128()));
+    }
+}
+
+/// Late payment penalty accrued on every overdue part of `offer` by `now`.
+fn penalty_owed(offer: &Offer, now: Timestamp) -> Result<Uint128, ContractError> {
+    let Some(terms) = &offer.late_penalty else {
+        return Ok(Uint128::zero());
+    };
+    if offer.schedule.is_empty() {
+        return late_penalty(terms.apr_bps, offer.amount, offer.active_till, now);
+    }
+    offer.schedule
+        .iter()
+        .filter(|i| !i.paid)
+        .try_fold(Uint128::zero(), |sum, i| {
+            let penalty = late_penalty(terms.apr_bps, i.principal, i.due, now)?;
+            sum.checked_add(penalty).map_err(|_| ContractError::InterestOverflow {})
+        })
+}
+
+/// Pays `cut` to each founder, their share of a late payment penalty.
+fn penalty_fee_msgs(storage: &dyn Storage, cut: Uint128) -> Result<Vec<BankMsg>, ContractError> {
+    if cut.is_zero() {
+        return Ok(vec![]);
+    }
+    Ok(FOUNDERS
+        .load(storage)?
+        .iter()
+        .map(|founder| BankMsg::Send {
+            to_address: founder.to_string(),
+            amount: coins(cut.u128(), "inj"),
+        })
+        .collect())
+}
+
+pub fn claim(
+    deps: DepsMut, 
+    env: Env, 
+    info: MessageInfo, 
+    collection_id: Uint128, 
+    offer_id: Uint128, 
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Claim)?;
+
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+
+    let index = offer_index(&offers, offer_id)?;
+    let curr_offer = &offers[index];
+
+    if curr_offer.lender != info.sender {
+        return Err(ContractError::NotLender {});
+    }
+
+    if curr_offer.status != Status::Taken && curr_offer.status != Status::Defaulted {
+        return Err(ContractError::NotPaid {});
+    }
+
+    // borrowers can't repay while repayments are paused, so loans that haven't
+    // defaulted yet can't be claimed from under them either
+    if curr_offer.status == Status::Taken {
+        ensure_not_paused(deps.as_ref(), Operation::Repay)?;
+    }
+
+    // the senior lender takes the collateral of an aggregated loan
+    if curr_offer.syndicate.first().is_some_and(|senior| *senior != offer_id) {
+        return Err(ContractError::NotSeniorLender {});
+    }
+
+    // a missed installment makes the loan claimable before it matures, once
+    // the penalty window for it has passed
+    if env.block.time < curr_offer.grace_end() {
+        return Err(ContractError::LoanActive {
+            active_till: curr_offer.grace_end()
+        });
+    }
+
+    // flagged collateral stays in escrow until it is unblocked
+    if let Some(token_id) = &curr_offer.token_id {
+        ensure_not_blocked(deps.as_ref(), collection_id, token_id)?;
+    }
+    if !curr_offer.bundle.is_empty() {
+        let collections = COLLECTIONS.load(deps.storage)?;
+        for item in curr_offer.bundle.iter() {
+            if let Some(i) = collections.iter().position(|c| c.addr == item.collection) {
+                ensure_not_blocked(deps.as_ref(), Uint128::from(i as u128), &item.token_id)?;
+            }
+        }
+    }
+
+    let wasm_msgs = release(&collection, curr_offer, &env.contract.address, &curr_offer.lender)?;
+    let data = ClaimResponse {
+        offer_id,
+        token_id: curr_offer.token_id.clone(),
+    };
+
+    let mut events = vec![];
+    for i in loan_members(&offers, index)? {
+        let offer = &mut offers[i];
+        if offer.status == Status::Taken {
+            transition(offer, Status::Defaulted, env.block.time)?;
+            record_default(deps.storage, collection_id)?;
+        }
+        record_claim(deps.storage, collection_id, offer)?;
+        transition(offer, Status::Closed, env.block.time)?;
+        unindex_maturity(deps.storage, collection_id, offer);
+        events.push(offer_event("claim", collection_id, offer, Uint128::zero()));
+    }
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    
+    Ok(Response::new()
+        .add_messages(wasm_msgs)
+        .add_events(events)
+        .set_data(to_json_binary(&data)?))
+}
+
+pub fn withdraw(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection_id: Uint128, 
+    offer_id: Uint128, 
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref(), Operation::Withdraw)?;
+
+    let collection = load_collection(deps.as_ref(), collection_id)?;
+    let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+
+
+    // let best_offer: Uint128 = get_best_offer(deps, collection_id)?;
+    let index = offer_index(&offers, offer_id)?;
+    let curr_offer = &offers[index];
+
+    if curr_offer.lender != info.sender {
+        return Err(ContractError::NotLender {});
+    }
+
+    if curr_offer.status != Status::Open {
+        return Err(ContractError::NotOpen {});
+    }
+
+    let transfer_msg = BankMsg::Send {
+        to_address: curr_offer.lender.clone().into(),
+        amount: coins(curr_offer.amount.u128(), "inj"),
+    };
+
+    let mut updated_offer = curr_offer.clone();
+    unindex_open(deps.storage, collection_id, &updated_offer);
+    transition(&mut updated_offer, Status::Cancelled, env.block.time)?;
+
+    let event = offer_event("withdraw", collection_id, &updated_offer, Uint128::zero());
+    let data = WithdrawResponse {
+        offer_id: updated_offer.offer_id,
+        amount: updated_offer.amount,
+    };
+    offers[index] = updated_offer;
+    OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_event(event)
+        .set_data(to_json_binary(&data)?))
+
+}
+
+/// Builds the `injective-bank` event describing the state of an offer after an action.
+pub(crate) fn offer_event(action: &str, collection_id: Uint128, offer: &Offer, fees: Uint128) -> Event {
+    let borrower = match &offer.borrower {
+        Some(addr) => addr.to_string(),
+        None => "none".to_string(),
+    };
+    let token_id = match &offer.token_id {
+        Some(t) => t.as_str(),
+        None => "none",
+    };
+    Event::new(EVENT_TYPE)
+        .add_attribute("action", action)
+        .add_attribute("collection_id", collection_id)
+        .add_attribute("offer_id", offer.offer_id)
+        .add_attribute("lender", offer.lender.as_str())
+        .add_attribute("borrower", borrower)
+        .add_attribute("token_id", token_id)
+        .add_attribute("amount", offer.amount)
+        .add_attribute("interest", offer.interest)
+        .add_attribute("fees", fees)
+        .add_attribute("status", offer.status.to_string())
+}
+
+/// Builds the `injective-bank` event for an action that doesn't change an offer.
+pub(crate) fn action_event(action: &str) -> Event {
+    Event::new(EVENT_TYPE).add_attribute("action", action)
+}
+
+/// Called by chain governance and, once registered, by Injective's begin blocker.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::UpdateParams {
+            founder_fee_bps,
+            offer_ttl,
+            keeper_batch_size,
+            allowed_code_ids,
+        } => {
+            if let Some(bps) = founder_fee_bps {
+                save_fees(deps.storage, bps, None)?;
+            }
+            let mut params = PARAMS.may_load(deps.storage)?.unwrap_or_default();
+            if let Some(ttl) = offer_ttl {
+                params.offer_ttl = ttl;
+            }
+            if let Some(size) = keeper_batch_size {
+                params.keeper_batch_size = size;
+            }
+            if let Some(code_ids) = allowed_code_ids {
+                params.allowed_code_ids = code_ids;
+            }
+            PARAMS.save(deps.storage, &params)?;
+            Ok(Response::new().add_event(action_event("update_params")))
+        },
+        SudoMsg::ForcePause { operations } => apply_pause(deps.storage, operations, true),
+        SudoMsg::ForceUnpause { operations } => apply_pause(deps.storage, operations, false),
+        SudoMsg::ForceEmergency { enabled } => apply_emergency(deps.storage, enabled),
+        SudoMsg::BeginBlocker {} => begin_blocker(deps, env),
+    }
+}
+
+/// Offers stored `token_id` as `Uint128`, which serializes as a decimal string,
+/// so numeric ids read back as the equivalent `String`. Re-saving every offer
+/// rewrites them under the current schema.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration { contract: version.contract });
+    }
+
+    // deployments from before roles only know their owner
+    if ROLES.keys(deps.storage, None, None, Order::Ascending).next().is_none() {
+        let owner = OWNER.load(deps.storage)?;
+        ROLES.save(deps.storage, &owner, &vec![Role::Admin])?;
+    }
+    if FEES.may_load(deps.storage)?.is_none() {
+        FEES.save(deps.storage, &FeeConfig::default())?;
+    }
+    if PAUSE.may_load(deps.storage)?.is_none() {
+        PAUSE.save(deps.storage, &PauseState::default())?;
+    }
+    if PARAMS.may_load(deps.storage)?.is_none() {
+        PARAMS.save(deps.storage, &Params::default())?;
+    }
+
+    let collections = match COLLECTIONS.load(deps.storage) {
+        Ok(collections) => collections,
+        Err(_) => migrate_percent_apy(deps.storage)?,
+    };
+    let mut migrated = 0_u64;
+    for (i, collection) in collections.iter().enumerate() {
+        let mut offers = OFFERS.may_load(deps.storage, collection.addr.clone())?.unwrap_or_default();
+        for offer in offers.iter_mut().filter(|offer| offer.apy_bps == 0) {
+            offer.apy_bps = collection.apy_bps;
+        }
+        // the baseline left repaid loans `paid` and claimed ones `failed`, with
+        // the collateral already returned or handed over
+        for offer in offers.iter_mut().filter(|offer| offer.history.is_empty()) {
+            if offer.status == Status::Paid || offer.status == Status::Defaulted {
+                offer.history.push(Transition { status: offer.status.clone(), at: env.block.time });
+                transition(offer, Status::Closed, env.block.time)?;
+            }
+        }
+        // loans taken and offers listed before the keeper indexes and stats
+        // existed, settled baseline loans were closed above
+        for offer in offers.iter() {
+            if offer.in_escrow() {
+                index_maturity(deps.storage, Uint128::from(i as u128), offer)?;
+            }
+            if offer.status == Status::Open {
+                index_open(deps.storage, Uint128::from(i as u128), offer)?;
+            }
+        }
+        // counted live once they exist, replay can't recover everything
+        if !STATS.has(deps.storage, i as u128) {
+            let mut stats = CollectionStats::default();
+            for offer in offers.iter() {
+                replay(&mut stats, offer)?;
+            }
+            STATS.save(deps.storage, i as u128, &stats)?;
+        }
+        migrated += offers.len() as u64;
+        OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", version.version)
+        .add_attribute("offers", migrated.to_string()))
+}
+
+/// Collections stored `apy` as a whole percentage (`90` = 90%) before rates
+/// moved to basis points.
+#[cw_serde]
+struct CollectionV1 {
+    addr: Addr,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    symbol: String,
+    apy: Uint128,
+    #[serde(default)]
+    valuation: Option<Valuation>,
+    #[serde(default)]
+    access: AccessMode,
+    #[serde(default)]
+    standard: TokenStandard,
+    #[serde(default)]
+    interest_mode: InterestMode,
+}
+
+fn migrate_percent_apy(storage: &mut dyn Storage) -> Result<Vec<Collection>, ContractError> {
+    let legacy: Item<Vec<CollectionV1>> = Item::new(COLLECTIONS_KEY);
+    let collections = legacy
+        .load(storage)?
+        .into_iter()
+        .map(|c| {
+            let apy_bps = c
+                .apy
+                .checked_mul(Uint128::from(100_u8))
+                .ok()
+                .and_then(|bps| u32::try_from(bps.u128()).ok())
+                .ok_or(ContractError::InterestOverflow {})?;
+            Ok(Collection {
+                addr: c.addr,
+                name: c.name,
+                symbol: c.symbol,
+                apy_bps,
+                valuation: c.valuation,
+                access: c.access,
+                standard: c.standard,
+                interest_mode: c.interest_mode,
+                late_penalty: None,
+            })
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+    COLLECTIONS.save(storage, &collections)?;
+    Ok(collections)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    match msg {
+        QueryMsg::GetBestOffer { collection_id, from } => {
+            let from = deps.api.addr_validate(&from)?;
+            Ok(to_json_binary(&get_best_offer(deps, collection_id, from)?)?)
+        },
+        QueryMsg::CollectionsList {} => Ok(to_json_binary(&get_collections_list(deps)?)?),
+        QueryMsg::PauseStatus {} => Ok(to_json_binary(&get_pause_status(deps)?)?),
+        QueryMsg::Roles { address } => Ok(to_json_binary(&get_roles(deps, address)?)?),
+        QueryMsg::IsAllowed { collection_id, address } => {
+            let address = deps.api.addr_validate(&address)?;
+            let collection = load_collection(deps, collection_id)?;
+            let allowed = is_allowed(deps, collection_id, &collection, &address)?;
+            Ok(to_json_binary(&IsAllowedResponse { allowed })?)
+        },
+        QueryMsg::BlockedTokens { collection_id, start_after, limit } => {
+            Ok(to_json_binary(&get_blocked_tokens(deps, collection_id, start_after, limit)?)?)
+        },
+        QueryMsg::CollectionStats { collection_id } => {
+            Ok(to_json_binary(&get_collection_stats(deps, collection_id)?)?)
+        },
+        QueryMsg::LoansExpiring { from, to, collection_id, limit, start_after } => {
+            Ok(to_json_binary(&get_loans_expiring(deps, from, to, collection_id, limit, start_after)?)?)
+        },
+        QueryMsg::Portfolio { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            Ok(to_json_binary(&get_portfolio(deps, address)?)?)
+        },
+        QueryMsg::LoanSchedule { collection_id, offer_id } => {
+            Ok(to_json_binary(&get_loan_schedule(deps, collection_id, offer_id)?)?)
+        },
+        QueryMsg::Params {} => Ok(to_json_binary(&PARAMS.may_load(deps.storage)?.unwrap_or_default())?),
+        QueryMsg::Fees {} => Ok(to_json_binary(&FEES.may_load(deps.storage)?.unwrap_or_default())?),
+        QueryMsg::GetOffers { collection_id, from } => {
+            let from = deps.api.addr_validate(&from)?;
+            Ok(to_json_binary(&get_offers(deps, collection_id, from)?)?)
+        },
+        QueryMsg::GetCollectionData { collection_id, from } => {
+            let from = deps.api.addr_validate(&from)?;
+            Ok(to_json_binary(&get_collections_data(deps, collection_id, from)?)?)
+        },
+    }
+}
+
+/// Loan cap an offer on `collection` is checked against. cw1155 offers are
+/// only checked once the amount pledged is known.
+fn offer_cap(deps: Deps, collection: &Collection) -> Result<Option<Uint128>, ContractError> {
+    if collection.standard == TokenStandard::Cw1155 {
+        return Ok(None);
+    }
+    loan_cap(deps, &collection.addr, &collection.valuation)
+}
+
+/// Loan cap for the pledged token of `collection` plus its bundle. The
+/// valuation prices a single token, so a cw1155 pledge scales it by the amount.
+/// Bundled tokens add the cap of their own collection, those without a
+/// valuation add nothing.
+fn pledge_cap(deps: Deps, collection: &Collection, pledge: &Pledge) -> Result<Option<Uint128>, ContractError> {
+    let mut cap = match loan_cap(deps, &collection.addr, &collection.valuation)? {
+        Some(cap) => cap.saturating_mul(pledge.token_amount.unwrap_or_else(Uint128::one)),
+        None => return Ok(None),
+    };
+    let bundle = &pledge.bundle;
+    if bundle.is_empty() {
+        return Ok(Some(cap));
+    }
+    let collections = COLLECTIONS.load(deps.storage)?;
+    for item in bundle.iter() {
+        let valuation = collections
+            .iter()
+            .find(|c| c.addr == item.collection)
+            .and_then(|c| c.valuation.clone());
+        let item_cap = loan_cap(deps, &item.collection, &valuation)?.unwrap_or_default();
+        cap = cap.saturating_add(item_cap);
+    }
+    Ok(Some(cap))
+}
+
+fn get_best_offer(deps: Deps, collection_id: Uint128, from: Addr) -> Result<Uint128, ContractError> {
+    let collection = load_collection(deps, collection_id)?;
+    let offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+    let cap = loan_cap(deps, &collection.addr, &collection.valuation)?;
+    let index = select_offer(&offers, cap, &from)?;
+    Ok(Uint128::from(index as u64))
+}
+
+/// Index of the best open offer `from` can take with collateral worth `cap`.
+fn select_offer(offers: &[Offer], cap: Option<Uint128>, from: &Addr) -> Result<usize, ContractError> {
+    let mut best: Option<(usize, &Offer)> = None;
+    let mut over_cap: Option<&Offer> = None;
+    for (i, offer) in offers.iter().enumerate() {
+        if offer.status != Status::Open || offer.lender == *from {
+            continue;
+        }
+        // offers above the collateral limit can't be taken
+        if check_ltv(offer.amount, cap).is_err() {
+            over_cap = Some(offer);
+            continue;
+        }
+        // prefer the largest amount, then the lowest interest
+        let better = match best {
+            None => true,
+            Some((_, current)) => {
+                offer.amount > current.amount
+                    || (offer.amount == current.amount && offer.interest < current.interest)
+                    || (offer.amount == current.amount && offer.interest == current.interest && offer.duration == current.duration)
+            }
+        };
+        if better {
+            best = Some((i, offer));
+        }
+    }
+    match (best, over_cap) {
+        (Some((i, _)), _) => Ok(i),
+        (None, Some(offer)) => Err(ContractError::LtvExceeded {
+            amount: offer.amount,
+            max: cap.unwrap_or_default(),
+        }),
+        (None, None) => Err(ContractError::NoOffer {}),
+    }
+}
+
+fn get_pause_status(deps: Deps) -> Result<PauseStatusResponse, ContractError> {
+    let state = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PauseStatusResponse {
+        paused: state.paused,
+        emergency: state.emergency,
+    })
+}
+
+fn get_roles(deps: Deps, address: String) -> Result<RolesResponse, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let roles = ROLES.may_load(deps.storage, &address)?.unwrap_or_default();
+    Ok(RolesResponse { address, roles })
+}
+
+fn get_blocked_tokens(
+    deps: Deps,
+    collection_id: Uint128,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<BlockedTokensResponse, ContractError> {
+    load_collection(deps, collection_id)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let tokens = BLOCKLIST
+        .prefix(collection_id.u128())
+        .keys(deps.storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(BlockedTokensResponse { tokens })
+}
+
+fn get_collection_stats(deps: Deps, collection_id: Uint128) -> Result<CollectionStatsResponse, ContractError> {
+    load_collection(deps, collection_id)?;
+    let stats = STATS.may_load(deps.storage, collection_id.u128())?.unwrap_or_default();
+    let loans = Uint128::from(stats.loans_taken);
+    let average = |total: Uint128| total.checked_div(loans).unwrap_or_default();
+    Ok(CollectionStatsResponse {
+        collection_id,
+        loans_taken: stats.loans_taken,
+        volume_lent: stats.volume_lent,
+        repaid: stats.repaid,
+        defaulted: stats.defaulted,
+        interest_paid: stats.interest_paid,
+        average_duration: average(stats.total_duration),
+        average_apy_bps: u32::try_from(average(stats.total_apy_bps).u128()).unwrap_or(u32::MAX),
+        outstanding: stats.outstanding,
+    })
+}
+
+fn get_loans_expiring(
+    deps: Deps,
+    from: Timestamp,
+    to: Timestamp,
+    collection_id: Option<Uint128>,
+    limit: Option<u32>,
+    start_after: Option<OfferRef>,
+) -> Result<LoansExpiringResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    if let Some(collection_id) = collection_id {
+        load_collection(deps, collection_id)?;
+    }
+    let load_offer = |collection_id: Uint128, offer_id: Uint128| -> Result<Offer, ContractError> {
+        let collection = load_collection(deps, collection_id)?;
+        let offers = OFFERS.load(deps.storage, collection.addr)?;
+        Ok(offers[offer_index(&offers, offer_id)?].clone())
+    };
+
+    let min = match start_after {
+        Some(after) => {
+            let offer = load_offer(after.collection_id, after.offer_id)?;
+            let key = (offer.active_till.nanos(), after.collection_id.u128(), after.offer_id.u128());
+            if key.0 < from.nanos() { Bound::inclusive((from.nanos(), 0, 0)) } else { Bound::exclusive(key) }
+        },
+        None => Bound::inclusive((from.nanos(), 0, 0)),
+    };
+    let max = Bound::inclusive((to.nanos(), u128::MAX, u128::MAX));
+
+    let mut loans = vec![];
+    for key in MATURITIES.keys(deps.storage, Some(min), Some(max), Order::Ascending) {
+        if loans.len() >= limit {
+            break;
+        }
+        let (_, id, offer_id) = key?;
+        if collection_id.is_some_and(|c| c.u128() != id) {
+            continue;
+        }
+        let offer = load_offer(Uint128::from(id), Uint128::from(offer_id))?;
+        loans.push(ExpiringLoan {
+            collection_id: Uint128::from(id),
+            offer_id: offer.offer_id,
+            active_till: offer.active_till,
+            claimable_at: offer.grace_end(),
+            status: offer.status,
+            lender: offer.lender,
+            borrower: offer.borrower,
+        });
+    }
+    Ok(LoansExpiringResponse { loans })
+}
+
+fn get_portfolio(deps: Deps, address: Addr) -> Result<PortfolioResponse, ContractError> {
+    let add = |a: Uint128, b: Uint128| a.checked_add(b).map_err(|_| ContractError::InterestOverflow {});
+    let mut portfolio = PortfolioResponse {
+        address: address.clone(),
+        collections: vec![],
+        principal_lent: Uint128::zero(),
+        principal_borrowed: Uint128::zero(),
+        interest_receivable: Uint128::zero(),
+        interest_payable: Uint128::zero(),
+        next_maturity: None,
+    };
+
+    for (i, collection) in COLLECTIONS.load(deps.storage)?.into_iter().enumerate() {
+        let mut summary = CollectionPortfolio {
+            collection_id: Uint128::from(i as u128),
+            open_offers: vec![],
+            open_amount: Uint128::zero(),
+            lending: vec![],
+            borrowing: vec![],
+            principal_lent: Uint128::zero(),
+            principal_borrowed: Uint128::zero(),
+            interest_receivable: Uint128::zero(),
+            interest_payable: Uint128::zero(),
+            next_maturity: None,
+        };
+        for offer in OFFERS.may_load(deps.storage, collection.addr)?.unwrap_or_default() {
+            if offer.status == Status::Open && offer.lender == address {
+                summary.open_offers.push(offer.offer_id);
+                summary.open_amount = add(summary.open_amount, offer.amount)?;
+                continue;
+            }
+            if offer.status != Status::Taken && offer.status != Status::Defaulted {
+                continue;
+            }
+            let lending = offer.lender == address;
+            let borrowing = offer.borrower.as_ref() == Some(&address);
+            if !lending && !borrowing {
+                continue;
+            }
+            let principal = offer.principal_owed();
+            let interest = offer.owed().ok_or(ContractError::InterestOverflow {})? - principal;
+            if lending {
+                summary.lending.push(offer.offer_id);
+                summary.principal_lent = add(summary.principal_lent, principal)?;
+                summary.interest_receivable = add(summary.interest_receivable, interest)?;
+            }
+            if borrowing {
+                summary.borrowing.push(offer.offer_id);
+                summary.principal_borrowed = add(summary.principal_borrowed, principal)?;
+                summary.interest_payable = add(summary.interest_payable, interest)?;
+            }
+            let due = offer.due();
+            summary.next_maturity = Some(summary.next_maturity.map_or(due, |next| next.min(due)));
+        }
+        if summary.open_offers.is_empty() && summary.lending.is_empty() && summary.borrowing.is_empty() {
+            continue;
+        }
+        portfolio.principal_lent = add(portfolio.principal_lent, summary.principal_lent)?;
+        portfolio.principal_borrowed = add(portfolio.principal_borrowed, summary.principal_borrowed)?;
+        portfolio.interest_receivable = add(portfolio.interest_receivable, summary.interest_receivable)?;
+        portfolio.interest_payable = add(portfolio.interest_payable, summary.interest_payable)?;
+        if let Some(maturity) = summary.next_maturity {
+            portfolio.next_maturity = Some(portfolio.next_maturity.map_or(maturity, |next| next.min(maturity)));
+        }
+        portfolio.collections.push(summary);
+    }
+    Ok(portfolio)
+}
+
+fn get_loan_schedule(deps: Deps, collection_id: Uint128, offer_id: Uint128) -> Result<LoanScheduleResponse, ContractError> {
+    let collection = load_collection(deps, collection_id)?;
+    let offers = OFFERS.load(deps.storage, collection.addr)?;
+    let offer = &offers[offer_index(&offers, offer_id)?];
+
+    let installments = match offer.status {
+        Status::Open | Status::Cancelled | Status::Expired => vec![],
+        _ if offer.schedule.is_empty() => vec![Installment {
+            due: offer.active_till,
+            principal: offer.amount,
+            interest: offer.interest,
+            paid: offer.transitioned_at(&Status::Paid).is_some(),
+        }],
+        _ => offer.schedule.clone(),
+    };
+    let remaining = installments
+        .iter()
+        .filter(|i| !i.paid)
+        .try_fold(Uint128::zero(), |sum, i| sum.checked_add(i.principal)?.checked_add(i.interest))
+        .map_err(|_| ContractError::InterestOverflow {})?;
+    Ok(LoanScheduleResponse { offer_id, installments, remaining })
+}
+
+fn get_collections_list(deps: Deps) -> Result<Vec<Collection>, ContractError> {
+    let collections = COLLECTIONS.load(deps.storage)?;
+    Ok(collections)
+}
+
+fn get_offers(deps: Deps, collection_id: Uint128, from: Addr) -> Result<OfferData, ContractError> {
+    let collection = load_collection(deps, collection_id)?;
+    let offers = OFFERS.load(deps.storage, collection.addr)?;
+    let borrowed: Vec<Offer> = offers
+        .iter()
+        .filter(|&x| x.borrower.clone() == Some(from.clone()))
+        .cloned()
+        .collect();
+    let lent: Vec<Offer> = offers
+        .iter()
+        .filter(|&x| x.lender.clone() == from.clone())
+        .cloned()
+        .collect();
+    let offer_data = OfferData {
+        lent,
+        borrowed
+    };
+    Ok(offer_data)
+}
+
+fn get_collections_data(deps: Deps, collection_id: Uint128, from: Addr) -> Result<CollectionData, ContractError> {
+    let collection = load_collection(deps, collection_id)?;
+    let offers = OFFERS.load(deps.storage, collection.addr)?;
+    let best_offer: Option<Offer> = match get_best_offer(deps, collection_id, from.clone()) {
+        Ok(best_offer) => Some(offers[offer_index(&offers, best_offer)?].clone()),
+        Err(_) => None,
+    };
+    let mut taken_offers = 0;
+    let mut total_pool: u128 = 0;
+    for offer in offers.iter() {
+        if offer.status != Status::Open {
+            taken_offers+=1;
+        } else {
+            total_pool+=offer.amount.u128();
+        }
+    }
+    let collection_data = CollectionData {
+        collection_id,
+        total_offers: Uint128::from(offers.len() as u64),
+        offers_taken: Uint128::from(taken_offers as u64),
+        best_offer,
+        total_pool: Uint128::from(total_pool)
+    };
+    Ok(collection_data)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{from_json, Attribute, ContractResult, CosmosMsg, WasmMsg, OwnedDeps, SystemResult, WasmQuery};
+    use crate::listing::tests::mock_collections;
+    use crate::msg::RegistryQueryMsg;
+    use cw_utils::PaymentError;
+
+
+    use super::*;
+
+    fn setup() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+        let instantiate_msg = InstantiateMsg {
+            founder_one: "foun1".to_string(),
+            founder_two: "foun2".to_string(),
+            guardian: Some("guardian".to_string()),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), instantiate_msg).unwrap();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_one".to_string(),
+            apy_bps: 9000,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        deps
+    }
+
+    fn bank_event(res: &Response) -> &Event {
+        res.events.iter().find(|e| e.ty == EVENT_TYPE).unwrap()
+    }
+
+    fn attr<'a>(event: &'a Event, key: &str) -> &'a str {
+        let found: Option<&Attribute> = event.attributes.iter().find(|a| a.key == key);
+        found.unwrap().value.as_str()
+    }
+
+    #[test]
+    fn test () {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+
+        // instantiate an empty contract
+        let instantiate_msg = InstantiateMsg {
+            founder_one: "foun1".to_string(),
+            founder_two: "foun1".to_string(),
+            guardian: None,
+        };
+        let info = mock_info("anyone", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_one".to_string(),
+            apy_bps: 9000,
+            standard: None,
+            interest_mode: None,
+        };
+
+        let info = mock_info("anyone", &[]);
+
+        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        let query_msg = QueryMsg::CollectionsList {};
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let collections: Vec<Collection> = from_json(res).unwrap();
+        assert_eq!(Addr::unchecked("coll_one"), collections[0].addr);
+
+        let add = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+
+        let info = mock_info("caller", &coins(
+            10000000000000000000, "inj"));
+
+        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
+        assert_eq!(2, res.messages.len());
+
+        let add = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+
+        let info = mock_info("caller", &coins(10000, "inj"));
+
+        let res = execute(deps.as_mut(), mock_env(), info, add).unwrap();
+        assert_eq!(2, res.messages.len());
+
+        let query_msg = QueryMsg::GetBestOffer { collection_id: Uint128::zero(), from: "borower".to_string() };
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let best: Uint128 = from_json(res).unwrap();
+        assert_eq!(Uint128::zero(), best);
+
+        let query_msg = QueryMsg::GetCollectionData { collection_id: Uint128::zero(), from: "borrower".to_string() };
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let data: CollectionData = from_json(res).unwrap();
+        assert_eq!(Uint128::from(2_u64), data.total_offers);
+        assert_eq!(Uint128::zero(), data.offers_taken);
+
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "0".to_string(),
+        };
+
+        let info = mock_info("borrower", &[]);
+
+        let res = execute(deps.as_mut(), mock_env(), info, borrow).unwrap();
+        assert_eq!(2, res.messages.len());
+
+        let query_msg = QueryMsg::GetOffers { collection_id: Uint128::zero(), from: "borrower".to_string() };
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let offers: OfferData = from_json(res).unwrap();
+        let borrower = match offers.borrowed[0].borrower.clone() {
+            Some(addr) => addr,
+            None => Addr::unchecked("none"),
+        };
+        assert_eq!(&borrower.to_string(), &String::from("borrower"));
+
+        let owed = offers.borrowed[0].amount + offers.borrowed[0].interest;
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+
+        let info = mock_info("borrower", &coins(owed.u128(), "inj"));
+        assert_eq!(borrower, info.sender.clone());
+
+        let res = execute(deps.as_mut(), mock_env(), info, repay).unwrap();
+        assert_eq!(2, res.messages.len());
+
+
+        let withdraw = ExecuteMsg::Withdraw {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(1_u64),
+        };
+
+        let info = mock_info("caller", &[]);
+
+        let res = execute(deps.as_mut(), mock_env(), info, withdraw).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn events () {
+        let mut deps = setup();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("lend", attr(event, "action"));
+        assert_eq!("0", attr(event, "collection_id"));
+        assert_eq!("0", attr(event, "offer_id"));
+        assert_eq!("lender", attr(event, "lender"));
+        assert_eq!("none", attr(event, "borrower"));
+        assert_eq!("none", attr(event, "token_id"));
+        assert_eq!("9900", attr(event, "amount"));
+        assert_eq!("100", attr(event, "fees"));
+        assert_eq!("open", attr(event, "status"));
+        let interest: Uint128 = attr(event, "interest").parse().unwrap();
+
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "7".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("borrow", attr(event, "action"));
+        assert_eq!("borrower", attr(event, "borrower"));
+        assert_eq!("7", attr(event, "token_id"));
+        assert_eq!("9900", attr(event, "amount"));
+        assert_eq!(interest.to_string(), attr(event, "interest"));
+        assert_eq!("0", attr(event, "fees"));
+        assert_eq!("taken", attr(event, "status"));
+
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let owed = Uint128::from(9900_u128) + interest;
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed.u128(), "inj")), repay).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("repay", attr(event, "action"));
+        assert_eq!("lender", attr(event, "lender"));
+        assert_eq!("borrower", attr(event, "borrower"));
+        assert_eq!("closed", attr(event, "status"));
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let withdraw = ExecuteMsg::Withdraw {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(1_u64),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), withdraw).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("withdraw", attr(event, "action"));
+        assert_eq!("1", attr(event, "offer_id"));
+        assert_eq!("cancelled", attr(event, "status"));
+
+        // admin actions use the same event type
+        let grant = ExecuteMsg::GrantRole { address: "guard2".to_string(), role: Role::Guardian };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("grant_role", attr(event, "action"));
+        assert_eq!("guard2", attr(event, "address"));
+        let pause = ExecuteMsg::Pause { operations: Some(vec![Operation::Lend]) };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), pause).unwrap();
+        assert_eq!("pause", attr(bank_event(&res), "action"));
+        assert!(res.attributes.is_empty());
+    }
+
+    #[test]
+    fn claim_event () {
+        let mut deps = setup();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "3".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let res = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        let event = bank_event(&res);
+        assert_eq!("claim", attr(event, "action"));
+        assert_eq!("0", attr(event, "collection_id"));
+        assert_eq!("0", attr(event, "offer_id"));
+        assert_eq!("lender", attr(event, "lender"));
+        assert_eq!("borrower", attr(event, "borrower"));
+        assert_eq!("3", attr(event, "token_id"));
+        assert_eq!("closed", attr(event, "status"));
+    }
+
+    #[test]
+    fn response_data () {
+        let mut deps = setup();
+
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let data: AddCollectionResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), data.collection_id);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(20000, "inj")), lend).unwrap();
+        let data: LendResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), data.offer_id);
+
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "3".to_string(),
+        };
+        let env = mock_env();
+        let res = execute(deps.as_mut(), env.clone(), mock_info("borrower", &[]), borrow).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), data.offer_id);
+        assert_eq!(Uint128::from(19800_u64), data.amount);
+        assert_eq!(env.block.time.plus_seconds(86400), data.due);
+
+        let owed = data.amount + data.interest;
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(1_u64),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed.u128(), "inj")), repay).unwrap();
+        let data: RepayResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), data.offer_id);
+        assert_eq!(owed, data.amount_paid);
+
+        let withdraw = ExecuteMsg::Withdraw {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), withdraw).unwrap();
+        let data: WithdrawResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::zero(), data.offer_id);
+        assert_eq!(Uint128::from(9900_u64), data.amount);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "4".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(60);
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(2_u64),
+        };
+        let res = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        let data: ClaimResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(2_u64), data.offer_id);
+        assert_eq!(Some("4".to_string()), data.token_id);
+    }
+
+    #[test]
+    fn lifecycle_history () {
+        let mut deps = setup();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "4".to_string(),
+        };
+        let taken_at = env.block.time.plus_seconds(5);
+        let mut borrow_env = mock_env();
+        borrow_env.block.time = taken_at;
+        execute(deps.as_mut(), borrow_env, mock_info("borrower", &[]), borrow).unwrap();
+
+        // repaying after maturity is rejected and leaves the loan taken
+        let mut late = mock_env();
+        late.block.time = taken_at.plus_seconds(60);
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let err = execute(deps.as_mut(), late.clone(), mock_info("borrower", &coins(20000, "inj")), repay).unwrap_err();
+        assert_eq!(ContractError::LoanExpired { active_till: taken_at.plus_seconds(60) }, err);
+
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        execute(deps.as_mut(), late.clone(), mock_info("lender", &[]), claim.clone()).unwrap();
+        let err = execute(deps.as_mut(), late.clone(), mock_info("lender", &[]), claim).unwrap_err();
+        assert_eq!(ContractError::NotPaid {}, err);
+
+        let query_msg = QueryMsg::GetOffers { collection_id: Uint128::zero(), from: "lender".to_string() };
+        let offers: OfferData = from_json(query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        let offer = &offers.lent[0];
+        assert_eq!(Status::Closed, offer.status);
+        assert_eq!(Some(env.block.time), offer.transitioned_at(&Status::Open));
+        assert_eq!(Some(taken_at), offer.transitioned_at(&Status::Taken));
+        assert_eq!(Some(late.block.time), offer.transitioned_at(&Status::Defaulted));
+        assert_eq!(Some(late.block.time), offer.transitioned_at(&Status::Closed));
+        assert_eq!(None, offer.transitioned_at(&Status::Paid));
+    }
+
+    #[test]
+    fn lend_interest_on_net_amount () {
+        let mut deps = setup();
+
+        // a year at 90% on the 990000 offered, not the 1000000 deposited
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(31_536_000_u64),
+            auto_relend: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(1_000_000, "inj")), lend).unwrap();
+        assert_eq!("990000", attr(bank_event(&res), "amount"));
+        assert_eq!("891000", attr(bank_event(&res), "interest"));
+    }
+
+    #[test]
+    fn invalid_inputs () {
+        let mut deps = setup();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::from(5_u64),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap_err();
+        assert_eq!(ContractError::InvalidCollectionId { collection_id: Uint128::from(5_u64) }, err);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::zero(),
+            auto_relend: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap_err();
+        assert_eq!(ContractError::InvalidDuration { duration: Uint128::zero() }, err);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::MAX,
+            auto_relend: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap_err();
+        assert_eq!(ContractError::InterestOverflow {}, err);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        // Decimal256 has room for interest on the largest possible deposit
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(u128::MAX, "inj")), lend).unwrap();
+        assert_eq!("411391887432607047481331210093148", attr(bank_event(&res), "interest"));
+
+        for msg in [
+            ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::from(3_u64) },
+            ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::from(3_u64) },
+            ExecuteMsg::Withdraw { collection_id: Uint128::zero(), offer_id: Uint128::from(3_u64) },
+        ] {
+            let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), msg).unwrap_err();
+            assert_eq!(ContractError::InvalidOfferId { offer_id: Uint128::from(3_u64) }, err);
+        }
+
+        let query_msg = QueryMsg::GetOffers { collection_id: Uint128::from(u64::MAX), from: "lender".to_string() };
+        let err = query(deps.as_ref(), mock_env(), query_msg).unwrap_err();
+        assert_eq!(ContractError::InvalidCollectionId { collection_id: Uint128::from(u64::MAX) }, err);
+
+        let query_msg = QueryMsg::GetCollectionData { collection_id: Uint128::zero(), from: "".to_string() };
+        let err = query(deps.as_ref(), mock_env(), query_msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "NotNormalized".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+
+        for apy_bps in [0, MAX_APY_BPS + 1] {
+            let add = ExecuteMsg::AddCollection {
+                collection_address: "coll_two".to_string(),
+                apy_bps,
+                standard: None,
+                interest_mode: None,
+            };
+            let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap_err();
+            assert_eq!(ContractError::InvalidApy { apy_bps, max: MAX_APY_BPS }, err);
+        }
+    }
+
+    #[test]
+    fn valuation_caps_loans () {
+        let mut deps = setup();
+
+        let valuation = Valuation {
+            source: PriceSource::Fixed { price: Uint128::from(20000_u128) },
+            max_ltv_bps: 5000,
+        };
+        let set = ExecuteMsg::SetValuation { collection_id: Uint128::zero(), valuation: Some(valuation.clone()) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), set.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::RiskManager }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+
+        let too_high = Valuation { max_ltv_bps: 10001, ..valuation };
+        let set = ExecuteMsg::SetValuation { collection_id: Uint128::zero(), valuation: Some(too_high) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap_err();
+        assert_eq!(ContractError::InvalidLtv { max_ltv_bps: 10001 }, err);
+
+        // 10100 deposited leaves 10000 after commission, 10200 leaves 10098
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10200, "inj")), lend.clone()).unwrap_err();
+        assert_eq!(ContractError::LtvExceeded { amount: Uint128::from(10098_u128), max: Uint128::from(10000_u128) }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10100, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(5000, "inj")), lend).unwrap();
+
+        // the floor drops, so only the smaller offer can be taken
+        let lower = Valuation {
+            source: PriceSource::Fixed { price: Uint128::from(12000_u128) },
+            max_ltv_bps: 5000,
+        };
+        let set = ExecuteMsg::SetValuation { collection_id: Uint128::zero(), valuation: Some(lower) };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+
+        let query_msg = QueryMsg::GetBestOffer { collection_id: Uint128::zero(), from: "borrower".to_string() };
+        let best: Uint128 = from_json(query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), best);
+
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(1_u64), data.offer_id);
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap_err();
+        assert_eq!(ContractError::LtvExceeded { amount: Uint128::from(10000_u128), max: Uint128::from(6000_u128) }, err);
+    }
+
+    #[test]
+    fn pause_and_emergency () {
+        let mut deps = setup();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+
+        let pause = ExecuteMsg::Pause { operations: Some(vec![Operation::Borrow]) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), pause.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Guardian }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), pause).unwrap();
+
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Borrow }, err);
+        // other operations keep working
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+
+        let status: PauseStatusResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap()).unwrap();
+        assert_eq!(PauseStatusResponse {
+            paused: vec![Operation::Borrow],
+            emergency: false,
+        }, status);
+
+        let unpause = ExecuteMsg::Unpause { operations: None };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), unpause).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap();
+        let loan: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+
+        let emergency = ExecuteMsg::SetEmergency { enabled: true };
+        execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), emergency).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Lend }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Borrow }, err);
+
+        // lenders can still pull open offers and borrowers can still repay
+        let withdraw = ExecuteMsg::Withdraw {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(1_u64),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), withdraw).unwrap();
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: loan.offer_id,
+        };
+        let owed = loan.amount + loan.interest;
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed.u128(), "inj")), repay).unwrap();
+
+        let pause_all = ExecuteMsg::Pause { operations: None };
+        execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), pause_all).unwrap();
+        let status: PauseStatusResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap()).unwrap();
+        assert_eq!(Operation::ALL.to_vec(), status.paused);
+        assert!(status.emergency);
+
+        // the admin can rotate the guardian out
+        let revoke = ExecuteMsg::RevokeRole { address: "guardian".to_string(), role: Role::Guardian };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), revoke).unwrap();
+        let emergency = ExecuteMsg::SetEmergency { enabled: false };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), emergency).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Guardian }, err);
+    }
+
+    #[test]
+    fn roles () {
+        let mut deps = setup();
+
+        let roles = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, address: &str| -> Vec<Role> {
+            let query_msg = QueryMsg::Roles { address: address.to_string() };
+            let res: RolesResponse = from_json(query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+            res.roles
+        };
+        assert_eq!(vec![Role::Admin], roles(&deps, "owner"));
+        assert_eq!(vec![Role::Guardian], roles(&deps, "guardian"));
+        assert!(roles(&deps, "risk").is_empty());
+
+        // listing and role management need Admin
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("guardian", &[]), add).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Admin }, err);
+        let grant = ExecuteMsg::GrantRole { address: "risk".to_string(), role: Role::RiskManager };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("risk", &[]), grant.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Admin }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+        assert_eq!(vec![Role::RiskManager], roles(&deps, "risk"));
+
+        let set = ExecuteMsg::SetValuation { collection_id: Uint128::zero(), valuation: None };
+        execute(deps.as_mut(), mock_env(), mock_info("risk", &[]), set).unwrap();
+        let pause = ExecuteMsg::Pause { operations: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("risk", &[]), pause).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Guardian }, err);
+
+        // fee changes need FeeManager
+        let fees = ExecuteMsg::UpdateFees { founder_fee_bps: 100, penalty_fee_bps: None };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("fees", &[]), fees.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::FeeManager }, err);
+        let grant = ExecuteMsg::GrantRole { address: "fees".to_string(), role: Role::FeeManager };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("fees", &[]), fees).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("fees", &[]), ExecuteMsg::UpdateFees { founder_fee_bps: 5001, penalty_fee_bps: None }).unwrap_err();
+        assert_eq!(ContractError::InvalidFee { founder_fee_bps: 5001 }, err);
+        let config: FeeConfig = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap()).unwrap();
+        assert_eq!(100, config.founder_fee_bps);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        assert_eq!("200", attr(bank_event(&res), "fees"));
+        assert_eq!("9800", attr(bank_event(&res), "amount"));
+
+        // the last admin can't be removed
+        let revoke = ExecuteMsg::RevokeRole { address: "owner".to_string(), role: Role::Admin };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), revoke.clone()).unwrap_err();
+        assert_eq!(ContractError::LastAdmin {}, err);
+        let grant = ExecuteMsg::GrantRole { address: "admin2".to_string(), role: Role::Admin };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("admin2", &[]), revoke).unwrap();
+        assert!(roles(&deps, "owner").is_empty());
+    }
+
+    #[test]
+    fn sudo_begin_blocker () {
+        let mut deps = setup();
+        let start = mock_env();
+
+        let update = SudoMsg::UpdateParams { founder_fee_bps: None, offer_ttl: Some(100), keeper_batch_size: Some(1), allowed_code_ids: None };
+        sudo(deps.as_mut(), start.clone(), update).unwrap();
+        let params: Params = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Params {}).unwrap()).unwrap();
+        assert_eq!(Params { offer_ttl: 100, keeper_batch_size: 1, allowed_code_ids: vec![] }, params);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), start.clone(), mock_info("lender", &coins(20000, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), start.clone(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), start.clone(), mock_info("borrower", &[]), borrow).unwrap();
+
+        // nothing is due yet
+        let mut env = start.clone();
+        env.block.time = start.block.time.plus_seconds(50);
+        for _ in 0..2 {
+            let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+            assert!(res.events.is_empty());
+        }
+
+        // paused operations hold back their transitions, emergency mode all of them
+        env.block.time = start.block.time.plus_seconds(100);
+        let pause = ExecuteMsg::Pause { operations: Some(vec![Operation::Withdraw, Operation::Repay]) };
+        execute(deps.as_mut(), start.clone(), mock_info("owner", &[]), pause).unwrap();
+        for _ in 0..2 {
+            let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+            assert!(res.events.is_empty() && res.messages.is_empty());
+        }
+        // nor can the lender claim a loan the borrower can't repay
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &[]), claim.clone()).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Repay }, err);
+        let unpause = ExecuteMsg::Unpause { operations: None };
+        execute(deps.as_mut(), start.clone(), mock_info("owner", &[]), unpause).unwrap();
+        sudo(deps.as_mut(), env.clone(), SudoMsg::ForceEmergency { enabled: true }).unwrap();
+        for _ in 0..2 {
+            let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+            assert!(res.events.is_empty() && res.messages.is_empty());
+        }
+        sudo(deps.as_mut(), env.clone(), SudoMsg::ForceEmergency { enabled: false }).unwrap();
+
+        // one offer per block: the matured loan first, then the stale offer
+        let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!("default", attr(bank_event(&res), "action"));
+        assert_eq!("defaulted", attr(bank_event(&res), "status"));
+        assert!(res.messages.is_empty());
+
+        let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!("expire", attr(bank_event(&res), "action"));
+        assert_eq!("1", attr(bank_event(&res), "offer_id"));
+        assert_eq!(1, res.messages.len());
+
+        // nothing is left in the indexes
+        let res = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert!(res.events.is_empty());
+
+        let withdraw = ExecuteMsg::Withdraw {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(1_u64),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &[]), withdraw).unwrap_err();
+        assert_eq!(ContractError::NotOpen {}, err);
+        let res = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        assert_eq!("closed", attr(bank_event(&res), "status"));
+    }
+
+    #[test]
+    fn sudo_governance () {
+        let mut deps = setup();
+
+        sudo(deps.as_mut(), mock_env(), SudoMsg::ForcePause { operations: Some(vec![Operation::Lend]) }).unwrap();
+        sudo(deps.as_mut(), mock_env(), SudoMsg::ForceEmergency { enabled: true }).unwrap();
+        let status: PauseStatusResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap()).unwrap();
+        assert_eq!(PauseStatusResponse { paused: vec![Operation::Lend], emergency: true }, status);
+
+        sudo(deps.as_mut(), mock_env(), SudoMsg::ForceUnpause { operations: None }).unwrap();
+        sudo(deps.as_mut(), mock_env(), SudoMsg::ForceEmergency { enabled: false }).unwrap();
+
+        let update = SudoMsg::UpdateParams { founder_fee_bps: Some(5001), offer_ttl: None, keeper_batch_size: None, allowed_code_ids: None };
+        let err = sudo(deps.as_mut(), mock_env(), update).unwrap_err();
+        assert_eq!(ContractError::InvalidFee { founder_fee_bps: 5001 }, err);
+        let update = SudoMsg::UpdateParams { founder_fee_bps: Some(0), offer_ttl: None, keeper_batch_size: None, allowed_code_ids: None };
+        sudo(deps.as_mut(), mock_env(), update).unwrap();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!("10000", attr(bank_event(&res), "amount"));
+    }
+
+    #[test]
+    fn access_modes () {
+        let mut deps = setup();
+
+        let is_allowed = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, address: &str| -> bool {
+            let query_msg = QueryMsg::IsAllowed { collection_id: Uint128::zero(), address: address.to_string() };
+            let res: IsAllowedResponse = from_json(query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+            res.allowed
+        };
+        assert!(is_allowed(&deps, "anyone"));
+
+        let set = ExecuteMsg::SetAccessMode { collection_id: Uint128::zero(), mode: AccessMode::Allowlist };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), set.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::Admin }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+        assert!(!is_allowed(&deps, "lender"));
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap_err();
+        assert_eq!(ContractError::NotAllowed { address: Addr::unchecked("lender") }, err);
+
+        let update = ExecuteMsg::UpdateAllowlist {
+            collection_id: Uint128::zero(),
+            add: vec!["lender".to_string(), "borrower".to_string(), "other".to_string()],
+            remove: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update).unwrap();
+        let update = ExecuteMsg::UpdateAllowlist {
+            collection_id: Uint128::zero(),
+            add: vec![],
+            remove: vec!["borrower".to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update).unwrap();
+        assert!(is_allowed(&deps, "lender"));
+        assert!(!is_allowed(&deps, "borrower"));
+
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap_err();
+        assert_eq!(ContractError::NotAllowed { address: Addr::unchecked("borrower") }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("other", &[]), borrow.clone()).unwrap();
+
+        // delegate to a registry that only admits "borrower"
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "registry" => {
+                let RegistryQueryMsg::IsAllowed { collection, address } = from_json(msg).unwrap();
+                assert_eq!("coll_one", collection);
+                let allowed = address == "borrower" || address == "lender";
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&IsAllowedResponse { allowed }).unwrap()))
+            }
+            _ => mock_collections(query),
+        });
+        let set = ExecuteMsg::SetAccessMode {
+            collection_id: Uint128::zero(),
+            mode: AccessMode::Registry { contract: Addr::unchecked("registry") },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+        assert!(is_allowed(&deps, "borrower"));
+        assert!(!is_allowed(&deps, "other"));
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+    }
+
+    #[test]
+    fn blocklist () {
+        let mut deps = setup();
+
+        let blocked = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, start_after: Option<&str>, limit: Option<u32>| -> Vec<String> {
+            let query_msg = QueryMsg::BlockedTokens {
+                collection_id: Uint128::zero(),
+                start_after: start_after.map(str::to_string),
+                limit,
+            };
+            let res: BlockedTokensResponse = from_json(query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+            res.tokens
+        };
+
+        let update = ExecuteMsg::UpdateBlocklist {
+            collection_id: Uint128::zero(),
+            block: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            unblock: vec![],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::RiskManager }, err);
+        let grant = ExecuteMsg::GrantRole { address: "risk".to_string(), role: Role::RiskManager };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("risk", &[]), update).unwrap();
+
+        assert_eq!(vec!["1".to_string(), "2".to_string(), "3".to_string()], blocked(&deps, None, None));
+        assert_eq!(vec!["2".to_string()], blocked(&deps, Some("1"), Some(1)));
+        assert_eq!(vec!["3".to_string()], blocked(&deps, Some("2"), None));
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "2".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap_err();
+        assert_eq!(ContractError::TokenBlocked { token_id: "2".to_string() }, err);
+
+        let update = ExecuteMsg::UpdateBlocklist {
+            collection_id: Uint128::zero(),
+            block: vec![],
+            unblock: vec!["2".to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("risk", &[]), update).unwrap();
+        assert_eq!(vec!["1".to_string(), "3".to_string()], blocked(&deps, None, None));
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+
+        // flagged after the loan was taken: the lender can't claim it
+        let update = ExecuteMsg::UpdateBlocklist {
+            collection_id: Uint128::zero(),
+            block: vec!["2".to_string()],
+            unblock: vec![],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), update).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let err = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap_err();
+        assert_eq!(ContractError::TokenBlocked { token_id: "2".to_string() }, err);
+    }
+
+    #[test]
+    fn listing () {
+        let mut deps = setup();
+
+        let collections: Vec<Collection> = from_json(query(deps.as_ref(), mock_env(), QueryMsg::CollectionsList {}).unwrap()).unwrap();
+        assert_eq!("coll_one name", collections[0].name);
+        assert_eq!("COLL_ONE", collections[0].symbol);
+
+        // listing the same address again would reset its offers
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(60_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_one".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap_err();
+        assert_eq!(ContractError::CollectionExists { address: Addr::unchecked("coll_one") }, err);
+        assert_eq!(1, OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap().len());
+
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "token".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap_err();
+        assert_eq!(ContractError::NotCw721 { address: Addr::unchecked("token") }, err);
+
+        let update = SudoMsg::UpdateParams { founder_fee_bps: None, offer_ttl: None, keeper_batch_size: None, allowed_code_ids: Some(vec![2]) };
+        sudo(deps.as_mut(), mock_env(), update).unwrap();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add.clone()).unwrap_err();
+        assert_eq!(ContractError::CodeIdNotAllowed { code_id: 1 }, err);
+
+        let update = SudoMsg::UpdateParams { founder_fee_bps: None, offer_ttl: None, keeper_batch_size: None, allowed_code_ids: Some(vec![1, 2]) };
+        sudo(deps.as_mut(), mock_env(), update).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+    }
+
+    #[test]
+    fn migrate_numeric_token_ids () {
+        use cosmwasm_schema::cw_serde;
+        use cw_storage_plus::Map;
+
+        // offer layout before token ids were strings
+        #[cw_serde]
+        struct LegacyOffer {
+            offer_id: Uint128,
+            amount: Uint128,
+            active_till: Timestamp,
+            interest: Uint128,
+            lender: Addr,
+            borrower: Option<Addr>,
+            token_id: Option<Uint128>,
+            status: Status,
+            duration: Uint128,
+        }
+        let legacy: Map<Addr, Vec<LegacyOffer>> = Map::new(crate::state::OFFERS_KEY);
+
+        let mut deps = setup();
+        let offer = LegacyOffer {
+            offer_id: Uint128::zero(),
+            amount: Uint128::from(10000_u64),
+            active_till: mock_env().block.time.plus_seconds(60),
+            interest: Uint128::from(12_u64),
+            lender: Addr::unchecked("lender"),
+            borrower: Some(Addr::unchecked("borrower")),
+            token_id: Some(Uint128::from(42_u64)),
+            status: Status::Taken,
+            duration: Uint128::from(60_u64),
+        };
+        legacy.save(&mut deps.storage, Addr::unchecked("coll_one"), &vec![offer]).unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(vec![("action", "migrate"), ("from_version", CONTRACT_VERSION), ("offers", "1")], res.attributes.iter().map(|a| (a.key.as_str(), a.value.as_str())).collect::<Vec<_>>());
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(Some("42".to_string()), offers[0].token_id);
+
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(10012, "inj")), repay).unwrap();
+        assert_eq!("42", attr(bank_event(&res), "token_id"));
+
+        set_contract_version(&mut deps.storage, "crates.io:other", "1.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        assert_eq!(ContractError::InvalidMigration { contract: "crates.io:other".to_string() }, err);
+    }
+
+    #[test]
+    fn migrate_percent_apy () {
+        use cosmwasm_schema::cw_serde;
+        use cw_storage_plus::Item;
+
+        // collection layout before rates were in basis points
+        #[cw_serde]
+        struct LegacyCollection {
+            addr: Addr,
+            apy: Uint128,
+        }
+        let legacy: Item<Vec<LegacyCollection>> = Item::new(crate::state::COLLECTIONS_KEY);
+
+        let mut deps = setup();
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let mut offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        offers[0].apy_bps = 0;
+        OFFERS.save(&mut deps.storage, Addr::unchecked("coll_one"), &offers).unwrap();
+        let collection = LegacyCollection { addr: Addr::unchecked("coll_one"), apy: Uint128::from(12_u64) };
+        legacy.save(&mut deps.storage, &vec![collection]).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let collections = COLLECTIONS.load(&deps.storage).unwrap();
+        assert_eq!(1200, collections[0].apy_bps);
+        assert_eq!(InterestMode::Compound, collections[0].interest_mode);
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(1200, offers[0].apy_bps);
+
+        // fractional rates are listed directly
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 750,
+            standard: None,
+            interest_mode: Some(InterestMode::Simple),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::one(),
+            duration: Uint128::from(crate::interest::SECONDS_PER_YEAR),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_two")).unwrap();
+        assert_eq!(750, offers[0].apy_bps);
+
+        // already migrated state is left alone
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(750, COLLECTIONS.load(&deps.storage).unwrap()[1].apy_bps);
+    }
+
+    #[test]
+    fn migrate_settled_baseline_offers () {
+        use cosmwasm_schema::cw_serde;
+        use cw_storage_plus::Map;
+
+        // statuses and offer layout the baseline wrote
+        #[cw_serde]
+        enum LegacyStatus {
+            Taken,
+            Paid,
+            Failed,
+        }
+        #[cw_serde]
+        struct LegacyOffer {
+            offer_id: Uint128,
+            amount: Uint128,
+            active_till: Timestamp,
+            interest: Uint128,
+            lender: Addr,
+            borrower: Option<Addr>,
+            token_id: Option<Uint128>,
+            status: LegacyStatus,
+            duration: Uint128,
+        }
+        let legacy: Map<Addr, Vec<LegacyOffer>> = Map::new(crate::state::OFFERS_KEY);
+
+        let mut deps = setup();
+        let now = mock_env().block.time;
+        let offer = |offer_id: u64, status: LegacyStatus, active_till: Timestamp| LegacyOffer {
+            offer_id: Uint128::from(offer_id),
+            amount: Uint128::from(1000_u64),
+            active_till,
+            interest: Uint128::from(10_u64),
+            lender: Addr::unchecked("lender"),
+            borrower: Some(Addr::unchecked("borrower")),
+            token_id: Some(Uint128::from(offer_id + 1)),
+            status,
+            duration: Uint128::from(60_u64),
+        };
+        let offers = vec![
+            offer(0, LegacyStatus::Failed, now.minus_seconds(100)),
+            offer(1, LegacyStatus::Paid, now.minus_seconds(100)),
+            offer(2, LegacyStatus::Taken, now.plus_seconds(60)),
+        ];
+        legacy.save(&mut deps.storage, Addr::unchecked("coll_one"), &offers).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        let settled = |status: Status| vec![Transition { status, at: now }, Transition { status: Status::Closed, at: now }];
+        assert_eq!((Status::Closed, settled(Status::Defaulted)), (offers[0].status.clone(), offers[0].history.clone()));
+        assert_eq!((Status::Closed, settled(Status::Paid)), (offers[1].status.clone(), offers[1].history.clone()));
+        assert_eq!((Status::Taken, vec![]), (offers[2].status.clone(), offers[2].history.clone()));
+
+        // the claimed token isn't handed over a second time
+        let claim = ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::zero() };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), claim).unwrap_err();
+        assert_eq!(ContractError::NotPaid {}, err);
+
+        let portfolio: PortfolioResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Portfolio { address: "lender".to_string() }).unwrap()).unwrap();
+        assert_eq!(vec![Uint128::from(2_u64)], portfolio.collections[0].lending);
+        assert_eq!(Uint128::from(1000_u64), portfolio.principal_lent);
+        let schedule_query = QueryMsg::LoanSchedule { collection_id: Uint128::zero(), offer_id: Uint128::one() };
+        let schedule: LoanScheduleResponse = from_json(query(deps.as_ref(), mock_env(), schedule_query).unwrap()).unwrap();
+        assert!(schedule.installments[0].paid);
+        assert!(schedule.remaining.is_zero());
+
+        // only the live loan is indexed by maturity
+        let expiring = QueryMsg::LoansExpiring { from: now.minus_seconds(1000), to: now.plus_seconds(1000), collection_id: None, limit: None, start_after: None };
+        let res: LoansExpiringResponse = from_json(query(deps.as_ref(), mock_env(), expiring).unwrap()).unwrap();
+        assert_eq!(vec![Uint128::from(2_u64)], res.loans.iter().map(|l| l.offer_id).collect::<Vec<_>>());
+
+        // settled baseline loans are left out of the backfilled stats
+        let stats: CollectionStatsResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::CollectionStats { collection_id: Uint128::zero() }).unwrap()).unwrap();
+        assert_eq!(
+            CollectionStatsResponse {
+                collection_id: Uint128::zero(),
+                loans_taken: 1,
+                volume_lent: Uint128::from(1000_u64),
+                repaid: 0,
+                defaulted: 0,
+                interest_paid: Uint128::zero(),
+                average_duration: Uint128::from(60_u64),
+                average_apy_bps: 9000,
+                outstanding: Uint128::from(1000_u64),
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn migrate_baseline_grants_owner_admin () {
+        use cosmwasm_schema::cw_serde;
+        use cw_storage_plus::Item;
+
+        #[cw_serde]
+        struct LegacyCollection {
+            addr: Addr,
+            apy: Uint128,
+        }
+
+        // what the baseline instantiate stored
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        OWNER.save(&mut deps.storage, &Addr::unchecked("owner")).unwrap();
+        FOUNDERS.save(&mut deps.storage, &vec![Addr::unchecked("foun1"), Addr::unchecked("foun2")]).unwrap();
+        let legacy: Item<Vec<LegacyCollection>> = Item::new(crate::state::COLLECTIONS_KEY);
+        legacy.save(&mut deps.storage, &vec![LegacyCollection { addr: Addr::unchecked("coll_one"), apy: Uint128::from(90_u64) }]).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let roles: RolesResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Roles { address: "owner".to_string() }).unwrap()).unwrap();
+        assert_eq!(vec![Role::Admin], roles.roles);
+        assert_eq!(FeeConfig::default(), FEES.load(&deps.storage).unwrap());
+        assert_eq!(PauseState::default(), PAUSE.load(&deps.storage).unwrap());
+        assert_eq!(Params::default(), PARAMS.load(&deps.storage).unwrap());
+
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 750,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let grant = ExecuteMsg::GrantRole { address: "guardian".to_string(), role: Role::Guardian };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), grant).unwrap();
+
+        // existing roles are kept on later migrations
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        let roles: RolesResponse = from_json(query(deps.as_ref(), mock_env(), QueryMsg::Roles { address: "guardian".to_string() }).unwrap()).unwrap();
+        assert_eq!(vec![Role::Guardian], roles.roles);
+    }
+
+    #[test]
+    fn cw1155_collateral () {
+        use crate::msg::Cw1155ExecuteMsg;
+
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "semi_one".to_string(),
+            apy_bps: 9000,
+            standard: Some(TokenStandard::Cw1155),
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let collection_id = Uint128::from(1_u64);
+
+        let lend = ExecuteMsg::Lend {
+            collection_id,
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+
+        // cw1155 collections are only borrowed against through the receive hook
+        let borrow = ExecuteMsg::Borrow {
+            collection_id,
+            token_id: "gold".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap_err();
+        assert_eq!(ContractError::WrongStandard { standard: TokenStandard::Cw1155 }, err);
+
+        let receive = |from: Option<&str>, amount: u128, collection_id: Uint128| ExecuteMsg::Receive(Cw1155ReceiveMsg {
+            operator: "borrower".to_string(),
+            from: from.map(str::to_string),
+            token_id: "gold".to_string(),
+            amount: Uint128::from(amount),
+            msg: to_json_binary(&Cw1155HookMsg::Borrow { collection_id }).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), receive(Some("borrower"), 5, collection_id)).unwrap_err();
+        assert_eq!(ContractError::NotCollection { collection: Addr::unchecked("semi_one") }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(None, 5, collection_id)).unwrap_err();
+        assert_eq!(ContractError::NoCollateralOwner {}, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(Some("borrower"), 0, collection_id)).unwrap_err();
+        assert_eq!(ContractError::NotEnoughTokens {}, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("coll_one", &[]), receive(Some("borrower"), 5, Uint128::zero())).unwrap_err();
+        assert_eq!(ContractError::WrongStandard { standard: TokenStandard::Cw721 }, err);
+
+        // tokens are already in escrow, only the loan is paid out
+        let res = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(Some("borrower"), 5, collection_id)).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!("borrower", attr(bank_event(&res), "borrower"));
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("semi_one")).unwrap();
+        assert_eq!(Some(Uint128::from(5_u64)), offers[data.offer_id.u128() as usize].token_amount);
+
+        let release = |recipient: &str| to_json_binary(&Cw1155ExecuteMsg::BatchSendFrom {
+            from: mock_env().contract.address.to_string(),
+            to: recipient.to_string(),
+            batch: vec![("gold".to_string(), Uint128::from(5_u64))],
+            msg: None,
+        }).unwrap();
+
+        let repay = ExecuteMsg::Repay {
+            collection_id,
+            offer_id: data.offer_id,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(20000, "inj")), repay).unwrap();
+        assert_eq!(
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "semi_one".to_string(), msg: release("borrower"), funds: vec![] }),
+            res.messages[1].msg
+        );
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(Some("borrower"), 5, collection_id)).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = ExecuteMsg::Claim {
+            collection_id,
+            offer_id: data.offer_id,
+        };
+        let res = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        assert_eq!(
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr: "semi_one".to_string(), msg: release("lender"), funds: vec![] }),
+            res.messages[0].msg
+        );
+    }
+
+    #[test]
+    fn cw1155_amount_scales_cap () {
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "semi_one".to_string(),
+            apy_bps: 9000,
+            standard: Some(TokenStandard::Cw1155),
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let collection_id = Uint128::from(1_u64);
+
+        // each token is worth 4000 at 50% LTV, so one token backs 2000
+        let valuation = Valuation {
+            source: PriceSource::Fixed { price: Uint128::from(4000_u128) },
+            max_ltv_bps: 5000,
+        };
+        let set = ExecuteMsg::SetValuation { collection_id, valuation: Some(valuation) };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+
+        // 10000 deposited leaves a 9900 offer
+        let lend = ExecuteMsg::Lend {
+            collection_id,
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+
+        let receive = |amount: u128| ExecuteMsg::Receive(Cw1155ReceiveMsg {
+            operator: "borrower".to_string(),
+            from: Some("borrower".to_string()),
+            token_id: "gold".to_string(),
+            amount: Uint128::from(amount),
+            msg: to_json_binary(&Cw1155HookMsg::Borrow { collection_id }).unwrap(),
+        });
+        let err = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(4)).unwrap_err();
+        assert_eq!(ContractError::LtvExceeded { amount: Uint128::from(9900_u128), max: Uint128::from(8000_u128) }, err);
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("semi_one", &[]), receive(5)).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("semi_one")).unwrap();
+        assert_eq!(Status::Taken, offers[data.offer_id.u128() as usize].status);
+    }
+
+    #[test]
+    fn bundle_loans () {
+        use cw721_base::msg::ExecuteMsg as Cw721ExecuteMsg;
+
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 9000,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+
+        let value = |collection_id: u64, price: u128| ExecuteMsg::SetValuation {
+            collection_id: Uint128::from(collection_id),
+            valuation: Some(Valuation { source: PriceSource::Fixed { price: Uint128::from(price) }, max_ltv_bps: 5000 }),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), value(0, 20000)).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), value(1, 8000)).unwrap();
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10100, "inj")), lend).unwrap();
+
+        // the floor drops below what a single token supports
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), value(0, 12000)).unwrap();
+        let borrow = ExecuteMsg::Borrow {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap_err();
+        assert_eq!(ContractError::LtvExceeded { amount: Uint128::from(10000_u128), max: Uint128::from(6000_u128) }, err);
+
+        let token = |collection_id: u64, token_id: &str| BundleToken { collection_id: Uint128::from(collection_id), token_id: token_id.to_string() };
+        let bundle = |tokens: Vec<BundleToken>| ExecuteMsg::BorrowBundle { collection_id: Uint128::zero(), tokens };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), bundle(vec![token(1, "7"), token(1, "8")])).unwrap_err();
+        assert_eq!(ContractError::InvalidBundle {}, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), bundle(vec![token(0, "1"), token(0, "1")])).unwrap_err();
+        assert_eq!(ContractError::InvalidBundle {}, err);
+
+        // 6000 + 4000 covers the offer
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), bundle(vec![token(1, "7"), token(0, "1")])).unwrap();
+        let escrow = |collection: &str, token_id: &str, recipient: &str| CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: collection.to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::<Empty, Empty>::TransferNft { recipient: recipient.to_string(), token_id: token_id.to_string() }).unwrap(),
+            funds: vec![],
+        });
+        let contract = mock_env().contract.address.to_string();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "borrower".to_string(), amount: coins(10000, "inj") }),
+                escrow("coll_two", "7", &contract),
+                escrow("coll_one", "1", &contract),
+            ],
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+        );
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(Some("1".to_string()), offers[0].token_id);
+        assert_eq!(vec![BundleItem { collection: Addr::unchecked("coll_two"), token_id: "7".to_string() }], offers[0].bundle);
+
+        // flagging any bundled token holds the whole bundle
+        let block = ExecuteMsg::UpdateBlocklist { collection_id: Uint128::from(1_u64), block: vec!["7".to_string()], unblock: vec![] };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), block).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let err = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap_err();
+        assert_eq!(ContractError::TokenBlocked { token_id: "7".to_string() }, err);
+
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(20000, "inj")), repay).unwrap();
+        assert_eq!(
+            vec![escrow("coll_one", "1", "borrower"), escrow("coll_two", "7", "borrower")],
+            res.messages.into_iter().skip(1).map(|m| m.msg).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn aggregated_loans () {
+        let mut deps = setup();
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        // 9900, 4950 and 1980 after founder fees
+        for (lender, deposit) in [("lender", 10000), ("second", 5000), ("third", 2000)] {
+            execute(deps.as_mut(), mock_env(), mock_info(lender, &coins(deposit, "inj")), lend.clone()).unwrap();
+        }
+
+        let aggregate = |token_id: &str, fill: Fill| ExecuteMsg::BorrowAggregated {
+            collection_id: Uint128::zero(),
+            token_id: token_id.to_string(),
+            fill,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), aggregate("1", Fill::TargetAmount(Uint128::from(20000_u64)))).unwrap_err();
+        assert_eq!(ContractError::TargetNotFilled { amount: Uint128::from(16830_u64) }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), aggregate("1", Fill::OfferIds(vec![Uint128::zero(), Uint128::zero()]))).unwrap_err();
+        assert_eq!(ContractError::InvalidOfferId { offer_id: Uint128::zero() }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("second", &[]), aggregate("1", Fill::OfferIds(vec![Uint128::zero(), Uint128::one()]))).unwrap_err();
+        assert_eq!(ContractError::IsLender {}, err);
+
+        // the two largest offers reach the target
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), aggregate("1", Fill::TargetAmount(Uint128::from(14000_u64)))).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::zero(), data.offer_id);
+        assert_eq!(Uint128::from(14850_u64), data.amount);
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send { to_address: "borrower".to_string(), amount: coins(14850, "inj") }),
+            res.messages[0].msg
+        );
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(vec![Uint128::zero(), Uint128::one()], offers[1].syndicate);
+        assert_eq!(Status::Open, offers[2].status);
+
+        // each lender gets back its own amount and interest
+        let repay = ExecuteMsg::Repay {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::one(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(20000, "inj")), repay).unwrap();
+        let share = |i: usize| (offers[i].amount + offers[i].interest).u128();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(share(0), "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "second".to_string(), amount: coins(share(1), "inj") }),
+            ],
+            res.messages.iter().take(2).map(|m| m.msg.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(3, res.messages.len());
+        let data: RepayResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(share(0) + share(1)), data.amount_paid);
+
+        // on default only the senior lender takes the token
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), aggregate("2", Fill::OfferIds(vec![Uint128::from(2_u64), Uint128::from(3_u64)]))).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = |offer_id: u64| ExecuteMsg::Claim {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::from(offer_id),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &[]), claim(3)).unwrap_err();
+        assert_eq!(ContractError::NotSeniorLender {}, err);
+        let res = execute(deps.as_mut(), env, mock_info("third", &[]), claim(2)).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(2, res.events.len());
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert!(offers[2..].iter().all(|o| o.status == Status::Closed));
+    }
+
+    #[test]
+    fn aggregated_loans_share_duration () {
+        let mut deps = setup();
+        // 9900 each after founder fees, for a day and for half a day
+        for (lender, duration) in [("lender", 86400_u64), ("second", 43200)] {
+            let lend = ExecuteMsg::Lend { collection_id: Uint128::zero(), duration: Uint128::from(duration), auto_relend: None };
+            execute(deps.as_mut(), mock_env(), mock_info(lender, &coins(10000, "inj")), lend).unwrap();
+        }
+        let day_interest = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap()[0].interest;
+
+        let aggregate = ExecuteMsg::BorrowAggregated {
+            collection_id: Uint128::zero(),
+            token_id: "1".to_string(),
+            fill: Fill::OfferIds(vec![Uint128::zero(), Uint128::one()]),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), aggregate).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(mock_env().block.time.plus_seconds(43200), data.due);
+
+        // both lenders earn interest for the half day the loan runs
+        let expected = interest(InterestMode::Compound, bps_rate(9000), Uint128::from(43200_u64), Uint128::from(9900_u64)).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        for offer in offers.iter() {
+            assert_eq!(Uint128::from(43200_u64), offer.duration);
+            assert_eq!(expected, offer.interest);
+        }
+        assert!(offers[0].interest < day_interest);
+        assert_eq!(expected + expected, data.interest);
+    }
+
+    #[test]
+    fn installment_loans () {
+        let mut deps = setup();
+        let period = 30 * 86400_u64;
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(3 * period),
+            auto_relend: None,
+        };
+        // 9900 after founder fees
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+
+        let borrow = |token_id: &str, installments: u32| ExecuteMsg::BorrowInstallments {
+            collection_id: Uint128::zero(),
+            token_id: token_id.to_string(),
+            installments,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow("1", 0)).unwrap_err();
+        assert_eq!(ContractError::InvalidInstallments { installments: 0 }, err);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow("1", 3)).unwrap();
+        let data: BorrowResponse = from_json(res.data.unwrap()).unwrap();
+
+        let schedule_query = QueryMsg::LoanSchedule { collection_id: Uint128::zero(), offer_id: Uint128::zero() };
+        let schedule: LoanScheduleResponse = from_json(query(deps.as_ref(), mock_env(), schedule_query.clone()).unwrap()).unwrap();
+        assert_eq!(vec![3300, 3300, 3300], schedule.installments.iter().map(|i| i.principal.u128()).collect::<Vec<_>>());
+        assert_eq!(mock_env().block.time.plus_seconds(period), schedule.installments[0].due);
+        assert_eq!(data.due, schedule.installments[2].due);
+        // interest is charged on the outstanding balance only
+        assert!(schedule.installments[0].interest > schedule.installments[1].interest);
+        let interest: Uint128 = schedule.installments.iter().map(|i| i.interest).sum();
+        assert_eq!(data.interest, interest);
+        assert_eq!(Uint128::from(9900_u64) + interest, schedule.remaining);
+
+        let pay = ExecuteMsg::PayInstallment { collection_id: Uint128::zero(), offer_id: Uint128::zero() };
+        let first = (schedule.installments[0].principal + schedule.installments[0].interest).u128();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(first - 1, "inj")), pay.clone()).unwrap_err();
+        assert_eq!(ContractError::NotEnoughINJ { amount: Uint128::from(first) }, err);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(first, "inj")), pay.clone()).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(first, "inj") })],
+            res.messages.iter().map(|m| m.msg.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!("0", attr(bank_event(&res), "installment"));
+        let data: PayInstallmentResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!((0, false), (data.installment, data.settled));
+
+        // missing the second installment lets the lender claim before maturity
+        let claim = ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::zero() };
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(period + 1);
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &[]), claim.clone()).unwrap_err();
+        assert_eq!(ContractError::LoanActive { active_till: schedule.installments[1].due }, err);
+        env.block.time = schedule.installments[1].due;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("borrower", &coins(10000, "inj")), pay.clone()).unwrap_err();
+        assert_eq!(ContractError::LoanExpired { active_till: schedule.installments[1].due }, err);
+        execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+
+        // paying every installment returns the collateral
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow("2", 2)).unwrap();
+        let pay = ExecuteMsg::PayInstallment { collection_id: Uint128::zero(), offer_id: Uint128::one() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(10000, "inj")), pay.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(10000, "inj")), pay.clone()).unwrap();
+        assert_eq!(2, res.messages.len());
+        let data: PayInstallmentResponse = from_json(res.data.unwrap()).unwrap();
+        assert!(data.settled);
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(Status::Closed, offers[1].status);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(10000, "inj")), pay).unwrap_err();
+        assert_eq!(ContractError::NotTaken {}, err);
+
+        // bullet loans have no installments to pay and a single scheduled payment
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let bullet = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: "3".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), bullet).unwrap();
+        let pay = ExecuteMsg::PayInstallment { collection_id: Uint128::zero(), offer_id: Uint128::from(2_u64) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(10000, "inj")), pay).unwrap_err();
+        assert_eq!(ContractError::NoSchedule {}, err);
+        let schedule_query = QueryMsg::LoanSchedule { collection_id: Uint128::zero(), offer_id: Uint128::from(2_u64) };
+        let schedule: LoanScheduleResponse = from_json(query(deps.as_ref(), mock_env(), schedule_query).unwrap()).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(1, schedule.installments.len());
+        assert_eq!(offers[2].amount + offers[2].interest, schedule.remaining);
+    }
+
+    #[test]
+    fn late_payment_penalty () {
+        let mut deps = setup();
+        let terms = LatePenalty { window: 86400, apr_bps: 3650 };
+        let set = ExecuteMsg::SetLatePenalty { collection_id: Uint128::zero(), penalty: Some(terms.clone()) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other", &[]), set.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized { role: Role::RiskManager }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), set).unwrap();
+        let fees = ExecuteMsg::UpdateFees { founder_fee_bps: 50, penalty_fee_bps: Some(5001) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), fees).unwrap_err();
+        assert_eq!(ContractError::InvalidPenaltyFee { penalty_fee_bps: 5001 }, err);
+        let fees = ExecuteMsg::UpdateFees { founder_fee_bps: 50, penalty_fee_bps: Some(1000) };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), fees).unwrap();
+
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        // 990000 after founder fees
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(1_000_000, "inj")), lend.clone()).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(1_000_000, "inj")), lend).unwrap();
+        for token_id in ["1", "2"] {
+            let borrow = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: token_id.to_string() };
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        }
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(Some(terms), offers[0].late_penalty);
+        let due = offers[0].active_till;
+        let grace_end = due.plus_seconds(86400);
+        assert_eq!(grace_end, offers[0].grace_end());
+
+        // half a day late at 0.1% a day, the founders take 10% each
+        let mut env = mock_env();
+        env.block.time = due.plus_seconds(43200);
+        let repay = |offer_id: u64| ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::from(offer_id) };
+        let owed = (offers[0].amount + offers[0].interest).u128() + 495;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("borrower", &coins(owed - 1, "inj")), repay(0)).unwrap_err();
+        assert_eq!(ContractError::NotEnoughINJ { amount: Uint128::from(owed) }, err);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("borrower", &coins(owed, "inj")), repay(0)).unwrap();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(owed - 98, "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "foun1".to_string(), amount: coins(49, "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "foun2".to_string(), amount: coins(49, "inj") }),
+            ],
+            res.messages.iter().take(3).map(|m| m.msg.clone()).collect::<Vec<_>>()
+        );
+        let data: RepayResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!((Uint128::from(owed), Uint128::from(495_u64)), (data.amount_paid, data.penalty));
+
+        // the keeper and the lender wait for the window to close
+        let claim = ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::one() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &[]), claim.clone()).unwrap_err();
+        assert_eq!(ContractError::LoanActive { active_till: grace_end }, err);
+        sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!(Status::Taken, OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap()[1].status);
+
+        env.block.time = grace_end;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("borrower", &coins(2_000_000, "inj")), repay(1)).unwrap_err();
+        assert_eq!(ContractError::LoanExpired { active_till: grace_end }, err);
+        sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!(Status::Defaulted, OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap()[1].status);
+        execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+    }
+
+    #[test]
+    fn auto_relend () {
+        let mut deps = setup();
+        let lend = |cycles: u32, with_interest: bool| ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: Some(AutoRelend { cycles, with_interest }),
+        };
+        let borrow = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: "1".to_string() };
+
+        // 9900 after founder fees, relent twice
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend(2, false)).unwrap();
+        for offer_id in 0..3_u64 {
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap();
+            let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+            let offer = &offers[offer_id as usize];
+            let owed = (offer.amount + offer.interest).u128();
+            let repay = ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::from(offer_id) };
+            let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed, "inj")), repay).unwrap();
+            let paid = if offer_id < 2 { offer.interest.u128() } else { owed };
+            assert_eq!(
+                CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(paid, "inj") }),
+                res.messages[0].msg
+            );
+        }
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(3, offers.len());
+        assert!(offers.iter().all(|o| o.amount == Uint128::from(9900_u64) && o.status == Status::Closed));
+        assert_eq!(Some(AutoRelend { cycles: 1, with_interest: false }), offers[1].auto_relend);
+        assert_eq!(None, offers[2].auto_relend);
+
+        // interest can be relent too, nothing is paid out
+        execute(deps.as_mut(), mock_env(), mock_info("second", &coins(10000, "inj")), lend(5, true)).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow.clone()).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        let owed = offers[3].amount + offers[3].interest;
+        let repay = ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::from(3_u64) };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed.u128(), "inj")), repay).unwrap();
+        assert_eq!(1, res.messages.len());
+        assert_eq!(vec!["repay", "relend"], res.events.iter().map(|e| attr(e, "action")).collect::<Vec<_>>());
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!((owed, Status::Open), (offers[4].amount, offers[4].status.clone()));
+
+        // lenders can opt out of further cycles
+        let stop = ExecuteMsg::StopAutoRelend { collection_id: Uint128::zero(), offer_id: Uint128::from(4_u64) };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), stop.clone()).unwrap_err();
+        assert_eq!(ContractError::NotLender {}, err);
+        execute(deps.as_mut(), mock_env(), mock_info("second", &[]), stop).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(None, offers[4].auto_relend);
+    }
+
+    #[test]
+    fn update_offer () {
+        let mut deps = setup();
+        let lend = ExecuteMsg::Lend {
+            collection_id: Uint128::zero(),
+            duration: Uint128::from(86400_u64),
+            auto_relend: None,
+        };
+        // 9900 after founder fees
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+        let update = |amount: Option<u128>, duration: Option<u64>, rate: Option<u32>| ExecuteMsg::UpdateOffer {
+            collection_id: Uint128::zero(),
+            offer_id: Uint128::zero(),
+            new_amount: amount.map(Uint128::from),
+            new_duration: duration.map(Uint128::from),
+            new_interest: rate,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("other", &[]), update(Some(5000), None, None)).unwrap_err();
+        assert_eq!(ContractError::NotLender {}, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(Some(0), None, None)).unwrap_err();
+        assert_eq!(ContractError::InvalidAmount {}, err);
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, None, Some(0))).unwrap_err();
+        assert_eq!(ContractError::InvalidApy { apy_bps: 0, max: MAX_APY_BPS }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, None, Some(MAX_APY_BPS + 1))).unwrap_err();
+        assert_eq!(ContractError::InvalidApy { apy_bps: MAX_APY_BPS + 1, max: MAX_APY_BPS }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::Pause { operations: Some(vec![Operation::Lend]) }).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, None, Some(1000))).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Lend }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, Some(60), None)).unwrap_err();
+        assert_eq!(ContractError::Paused { operation: Operation::Lend }, err);
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::Unpause { operations: Some(vec![Operation::Lend]) }).unwrap();
+
+        // lend and update price interest on the same amount
+        let lent = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap()[0].clone();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, None, None)).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(lent, OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap()[0]);
+
+        // topping up 5000 costs 5050 with founder fees, the rest is returned
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(5000, "inj")), update(Some(14900), None, None)).unwrap_err();
+        assert_eq!(ContractError::NotEnoughINJ { amount: Uint128::from(5050_u64) }, err);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(5100, "inj")), update(Some(14900), None, None)).unwrap();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send { to_address: "foun1".to_string(), amount: coins(25, "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "foun2".to_string(), amount: coins(25, "inj") }),
+                CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(50, "inj") }),
+            ],
+            res.messages.iter().map(|m| m.msg.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!("50", attr(bank_event(&res), "fees"));
+        let data: UpdateOfferResponse = from_json(res.data.unwrap()).unwrap();
+        let expected = interest(InterestMode::Compound, bps_rate(9000), Uint128::from(86400_u64), Uint128::from(14900_u64)).unwrap();
+        assert_eq!((Uint128::from(14900_u64), expected), (data.amount, data.interest));
+
+        // lowering the amount refunds the difference and takes no funds
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(1, "inj")), update(Some(4900), None, None)).unwrap_err();
+        assert_eq!(ContractError::Payment(PaymentError::NonPayable {}), err);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(Some(4900), Some(172800), Some(750))).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send { to_address: "lender".to_string(), amount: coins(10000, "inj") })],
+            res.messages.iter().map(|m| m.msg.clone()).collect::<Vec<_>>()
+        );
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        assert_eq!(750, offers[0].apy_bps);
+        assert_eq!(Uint128::from(172800_u64), offers[0].duration);
+        let expected = interest(InterestMode::Compound, bps_rate(750), Uint128::from(172800_u64), Uint128::from(4900_u64)).unwrap();
+        assert_eq!(expected, offers[0].interest);
+
+        let borrow = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), update(None, Some(60), None)).unwrap_err();
+        assert_eq!(ContractError::NotOpen {}, err);
+    }
+
+    #[test]
+    fn batch_messages () {
+        use crate::msg::{BatchClaimResponse, BatchLendResponse, BatchRepayResponse, BatchWithdrawResponse, LendItem, OfferRef, RepayItem};
+
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        let item = |collection_id: u64, amount: u128| LendItem {
+            collection_id: Uint128::from(collection_id),
+            duration: Uint128::from(86400_u64),
+            amount: Uint128::from(amount),
+            auto_relend: None,
+        };
+        let offer = |collection_id: u64, offer_id: u64| OfferRef {
+            collection_id: Uint128::from(collection_id),
+            offer_id: Uint128::from(offer_id),
+        };
+
+        // the funds must cover every item exactly
+        let batch = ExecuteMsg::BatchLend { items: vec![item(0, 10000), item(1, 20000), item(0, 10000)] };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(39999, "inj")), batch.clone()).unwrap_err();
+        assert_eq!(ContractError::FundsMismatch { denom: "inj".to_string(), expected: Uint128::from(40000_u64), received: Uint128::from(39999_u64) }, err);
+        let funds = vec![cosmwasm_std::Coin::new(40000, "inj"), cosmwasm_std::Coin::new(1, "usdt")];
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &funds), batch.clone()).unwrap_err();
+        assert_eq!(ContractError::FundsMismatch { denom: "usdt".to_string(), expected: Uint128::zero(), received: Uint128::one() }, err);
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), ExecuteMsg::BatchLend { items: vec![] }).unwrap_err();
+        assert_eq!(ContractError::BatchSize { size: 0, max: crate::batch::MAX_BATCH }, err);
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(40000, "inj")), batch).unwrap();
+        let data: BatchLendResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(vec![Uint128::zero(), Uint128::zero(), Uint128::one()], data.results.iter().map(|r| r.offer_id).collect::<Vec<_>>());
+        assert_eq!(4, res.events.len());
+        assert_eq!("batch_lend", attr(&res.events[0], "action"));
+        assert_eq!("3", attr(&res.events[0], "items"));
+        // founder fees of every item
+        assert_eq!(6, res.messages.len());
+
+        // a failing item fails the batch, the chain reverts what came before it
+        let borrow = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let borrow = ExecuteMsg::Borrow { collection_id: Uint128::one(), token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let offers = OFFERS.load(&deps.storage, Addr::unchecked("coll_one")).unwrap();
+        let taken = offers.iter().position(|o| o.status == Status::Taken).unwrap() as u64;
+        let open = 1 - taken;
+        let withdraw = ExecuteMsg::BatchWithdraw { offers: vec![offer(0, taken), offer(0, open)] };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), withdraw).unwrap_err();
+        assert_eq!(ContractError::NotOpen {}, err);
+        let withdraw = ExecuteMsg::BatchWithdraw { offers: vec![offer(0, open)] };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("lender", &[]), withdraw).unwrap();
+        let data: BatchWithdrawResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::from(9900_u64), data.results[0].amount);
+
+        // repaying both loans in one message
+        let owed = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, addr: &str, id: u64| {
+            let offers = OFFERS.load(&deps.storage, Addr::unchecked(addr)).unwrap();
+            offers[id as usize].amount + offers[id as usize].interest
+        };
+        let items = vec![
+            RepayItem { collection_id: Uint128::zero(), offer_id: Uint128::from(taken), amount: owed(&deps, "coll_one", taken) },
+            RepayItem { collection_id: Uint128::one(), offer_id: Uint128::zero(), amount: owed(&deps, "coll_two", 0) },
+        ];
+        let total = items.iter().map(|i| i.amount.u128()).sum::<u128>();
+        let res = execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(total, "inj")), ExecuteMsg::BatchRepay { items }).unwrap();
+        let data: BatchRepayResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(total, data.results.iter().map(|r| r.amount_paid.u128()).sum::<u128>());
+
+        // and claiming two defaults
+        let batch = ExecuteMsg::BatchLend { items: vec![item(0, 10000), item(1, 10000)] };
+        execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(20000, "inj")), batch).unwrap();
+        for collection_id in [Uint128::zero(), Uint128::one()] {
+            let borrow = ExecuteMsg::Borrow { collection_id, token_id: "2".to_string() };
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        }
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let claim = ExecuteMsg::BatchClaim { offers: vec![offer(0, 2), offer(1, 1)] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lender", &coins(1, "inj")), claim.clone()).unwrap_err();
+        assert_eq!(ContractError::FundsMismatch { denom: "inj".to_string(), expected: Uint128::zero(), received: Uint128::one() }, err);
+        let res = execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        let data: BatchClaimResponse = from_json(res.data.unwrap()).unwrap();
+        assert_eq!(vec![Some("2".to_string()), Some("2".to_string())], data.results.into_iter().map(|r| r.token_id).collect::<Vec<_>>());
+        assert_eq!(2, res.messages.len());
+    }
+
+    #[test]
+    fn portfolio () {
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        // 9900 after founder fees
+        for (collection_id, funds) in [(0_u64, 10000), (0, 20000), (1, 10000)] {
+            let lend = ExecuteMsg::Lend {
+                collection_id: Uint128::from(collection_id),
+                duration: Uint128::from(86400_u64),
+                auto_relend: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(funds, "inj")), lend).unwrap();
+        }
+        let borrow = ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id: "1".to_string() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        let borrow = ExecuteMsg::BorrowInstallments { collection_id: Uint128::one(), token_id: "1".to_string(), installments: 2 };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+
+        let portfolio = |address: &str| -> PortfolioResponse {
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::Portfolio { address: address.to_string() }).unwrap()).unwrap()
+        };
+        let lender = portfolio("lender");
+        assert_eq!(2, lender.collections.len());
+        // the larger offer was taken
+        assert_eq!(vec![Uint128::zero()], lender.collections[0].open_offers);
+        assert_eq!(Uint128::from(9900_u64), lender.collections[0].open_amount);
+        assert_eq!(vec![Uint128::one()], lender.collections[0].lending);
+        assert_eq!(Uint128::from(19800_u64 + 9900), lender.principal_lent);
+        assert!(lender.collections[1].borrowing.is_empty());
+        // the first installment is due half way
+        assert_eq!(Some(mock_env().block.time.plus_seconds(43200)), lender.next_maturity);
+        assert_eq!(Some(mock_env().block.time.plus_seconds(86400)), lender.collections[0].next_maturity);
+
+        let borrower = portfolio("borrower");
+        assert_eq!(lender.principal_lent, borrower.principal_borrowed);
+        assert_eq!(lender.interest_receivable, borrower.interest_payable);
+        assert!(!borrower.interest_payable.is_zero());
+        assert!(borrower.collections.iter().all(|c| c.open_offers.is_empty() && c.lending.is_empty()));
+
+        assert!(portfolio("other").collections.is_empty());
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::Portfolio { address: "".to_string() }).is_err());
+    }
+
+    #[test]
+    fn loans_expiring () {
+        let mut deps = setup();
+        let add = ExecuteMsg::AddCollection {
+            collection_address: "coll_two".to_string(),
+            apy_bps: 1000,
+            standard: None,
+            interest_mode: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), add).unwrap();
+        // each offer is taken right away, in order
+        for (collection_id, duration, token_id) in [(0_u64, 300_u64, "1"), (0, 100, "2"), (1, 150, "1"), (0, 200, "3")] {
+            let lend = ExecuteMsg::Lend {
+                collection_id: Uint128::from(collection_id),
+                duration: Uint128::from(duration),
+                auto_relend: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(10000, "inj")), lend).unwrap();
+            let borrow = ExecuteMsg::Borrow { collection_id: Uint128::from(collection_id), token_id: token_id.to_string() };
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        }
+        let now = mock_env().block.time;
+        let expiring = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, to: u64, collection_id: Option<u64>, limit: Option<u32>, start_after: Option<(u64, u64)>| -> Vec<(u64, u64)> {
+            let msg = QueryMsg::LoansExpiring {
+                from: now,
+                to: now.plus_seconds(to),
+                collection_id: collection_id.map(Uint128::from),
+                limit,
+                start_after: start_after.map(|(c, o)| OfferRef { collection_id: Uint128::from(c), offer_id: Uint128::from(o) }),
+            };
+            let res: LoansExpiringResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            res.loans.iter().map(|l| (l.collection_id.u128() as u64, l.offer_id.u128() as u64)).collect()
+        };
+
+        assert_eq!(vec![(0, 1), (1, 0), (0, 2)], expiring(&deps, 250, None, None, None));
+        assert_eq!(vec![(0, 1), (1, 0), (0, 2), (0, 0)], expiring(&deps, 300, None, None, None));
+        assert_eq!(vec![(0, 1), (1, 0)], expiring(&deps, 300, None, Some(2), None));
+        assert_eq!(vec![(0, 2), (0, 0)], expiring(&deps, 300, None, Some(2), Some((1, 0))));
+        assert_eq!(vec![(0, 1), (0, 2)], expiring(&deps, 250, Some(0), None, None));
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::LoansExpiring {
+            from: now, to: now, collection_id: Some(Uint128::from(5_u64)), limit: None, start_after: None,
+        }).is_err());
+
+        let msg = QueryMsg::LoansExpiring { from: now, to: now.plus_seconds(100), collection_id: None, limit: None, start_after: None };
+        let res: LoansExpiringResponse = from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(now.plus_seconds(100), res.loans[0].active_till);
+        assert_eq!(now.plus_seconds(100), res.loans[0].claimable_at);
+        assert_eq!(Status::Taken, res.loans[0].status);
+        assert_eq!(Some(Addr::unchecked("borrower")), res.loans[0].borrower);
+
+        // closed loans leave the index, defaulted ones stay until claimed
+        let offers = OFFERS.load(deps.as_ref().storage, Addr::unchecked("coll_one")).unwrap();
+        let owed = offers[1].owed().unwrap();
+        let repay = ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::one() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(owed.u128(), "inj")), repay).unwrap();
+        assert_eq!(vec![(1, 0), (0, 2)], expiring(&deps, 250, None, None, None));
+
+        let mut env = mock_env();
+        env.block.time = now.plus_seconds(200);
+        sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!(vec![(1, 0), (0, 2)], expiring(&deps, 250, None, None, None));
+        let claim = ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::from(2_u64) };
+        execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        assert_eq!(vec![(1, 0)], expiring(&deps, 250, None, None, None));
+    }
+
+    #[test]
+    fn collection_stats () {
+        let mut deps = setup();
+        let stats = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| -> CollectionStatsResponse {
+            from_json(query(deps.as_ref(), mock_env(), QueryMsg::CollectionStats { collection_id: Uint128::zero() }).unwrap()).unwrap()
+        };
+        assert_eq!(0, stats(&deps).loans_taken);
+        assert!(stats(&deps).average_duration.is_zero());
+
+        // a bullet loan, an installment loan and one left to default
+        for (funds, duration, installments) in [(10000_u128, 86400_u64, None), (20000, 43200, Some(2)), (10000, 100, None)] {
+            let lend = ExecuteMsg::Lend { collection_id: Uint128::zero(), duration: Uint128::from(duration), auto_relend: None };
+            execute(deps.as_mut(), mock_env(), mock_info("lender", &coins(funds, "inj")), lend).unwrap();
+            let token_id = (funds + duration as u128).to_string();
+            let borrow = match installments {
+                Some(installments) => ExecuteMsg::BorrowInstallments { collection_id: Uint128::zero(), token_id, installments },
+                None => ExecuteMsg::Borrow { collection_id: Uint128::zero(), token_id },
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &[]), borrow).unwrap();
+        }
+        let res = stats(&deps);
+        assert_eq!(3, res.loans_taken);
+        assert_eq!(Uint128::from(39600_u64), res.volume_lent);
+        assert_eq!(res.volume_lent, res.outstanding);
+        assert_eq!(Uint128::from(43233_u64), res.average_duration);
+        assert_eq!(9000, res.average_apy_bps);
+
+        let offers = OFFERS.load(deps.as_ref().storage, Addr::unchecked("coll_one")).unwrap();
+        let repay = ExecuteMsg::Repay { collection_id: Uint128::zero(), offer_id: Uint128::zero() };
+        execute(deps.as_mut(), mock_env(), mock_info("borrower", &coins(offers[0].owed().unwrap().u128(), "inj")), repay).unwrap();
+        assert_eq!(1, stats(&deps).repaid);
+        assert_eq!(Uint128::from(29700_u64), stats(&deps).outstanding);
+        for installment in offers[1].schedule.iter() {
+            let pay = ExecuteMsg::PayInstallment { collection_id: Uint128::zero(), offer_id: Uint128::one() };
+            let funds = coins((installment.principal + installment.interest).u128(), "inj");
+            execute(deps.as_mut(), mock_env(), mock_info("borrower", &funds), pay).unwrap();
+        }
+        let res = stats(&deps);
+        assert_eq!(2, res.repaid);
+        assert_eq!(Uint128::from(9900_u64), res.outstanding);
+        assert_eq!(offers[0].interest + offers[1].interest, res.interest_paid);
+
+        // a default still counts as outstanding until the collateral is claimed
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {}).unwrap();
+        assert_eq!(1, stats(&deps).defaulted);
+        assert_eq!(Uint128::from(9900_u64), stats(&deps).outstanding);
+        let claim = ExecuteMsg::Claim { collection_id: Uint128::zero(), offer_id: Uint128::from(2_u64) };
+        execute(deps.as_mut(), env, mock_info("lender", &[]), claim).unwrap();
+        let res = stats(&deps);
+        assert_eq!((2, 1), (res.repaid, res.defaulted));
+        assert!(res.outstanding.is_zero());
+
+        // migration rebuilds the same figures from the offers when there are none
+        let live = STATS.load(deps.as_ref().storage, 0).unwrap();
+        STATS.remove(deps.as_mut().storage, 0);
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(live, STATS.load(deps.as_ref().storage, 0).unwrap());
+        // and keeps the live counters otherwise
+        let bumped = CollectionStats { defaulted: 5, ..live };
+        STATS.save(deps.as_mut().storage, 0, &bumped).unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(bumped, STATS.load(deps.as_ref().storage, 0).unwrap());
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::CollectionStats { collection_id: Uint128::from(9_u64) }).is_err());
+    }
+}
+
+#[cfg(test)]
+mod fuzz {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use crate::listing::tests::mock_collections;
+    use cosmwasm_std::{from_json, Coin};
+    use proptest::prelude::*;
+    use crate::msg::{LendItem, OfferRef, RepayItem};
+
+    use super::*;
+
+    const SENDERS: [&str; 5] = ["owner", "lender", "borrower", "other", "guardian"];
+
+    fn id() -> impl Strategy<Value = Uint128> {
+        prop_oneof![
+            4 => (0_u128..4).prop_map(Uint128::from),
+            1 => any::<u128>().prop_map(Uint128::from),
+        ]
+    }
+
+    fn token_id() -> impl Strategy<Value = String> {
+        prop_oneof![
+            4 => (0_u128..4).prop_map(|t| t.to_string()),
+            1 => ".{0,16}",
+        ]
+    }
+
+    fn duration() -> impl Strategy<Value = Uint128> {
+        prop_oneof![
+            4 => (0_u128..200_000).prop_map(Uint128::from),
+            1 => any::<u128>().prop_map(Uint128::from),
+        ]
+    }
+
+    fn amount() -> impl Strategy<Value = Uint128> {
+        prop_oneof![
+            4 => (0_u128..2_000_000).prop_map(Uint128::from),
+            1 => any::<u128>().prop_map(Uint128::from),
+        ]
+    }
+
+    fn funds() -> impl Strategy<Value = Vec<Coin>> {
+        prop_oneof![
+            1 => Just(vec![]),
+            4 => (1_u128..1_000_000_000).prop_map(|a| coins(a, "inj")),
+            1 => any::<u128>().prop_map(|a| coins(a.max(1), "inj")),
+            1 => (1_u128..1000).prop_map(|a| coins(a, "usdt")),
+        ]
+    }
+
+    fn execute_msg() -> impl Strategy<Value = ExecuteMsg> {
+        prop_oneof![
+            (
+                prop::sample::select(vec!["coll_one", "coll_two", "Bad Address", ""]),
+                any::<u32>(),
+                prop::option::of(prop::sample::select(vec![InterestMode::Simple, InterestMode::Compound, InterestMode::Continuous])),
+            )
+                .prop_map(|(addr, apy, interest_mode)| ExecuteMsg::AddCollection {
+                    collection_address: addr.to_string(),
+                    apy_bps: apy % 100_000,
+                    standard: None,
+                    interest_mode,
+                }),
+            (id(), duration(), prop::option::of((0_u32..4, any::<bool>()))).prop_map(|(collection_id, duration, relend)| ExecuteMsg::Lend {
+                collection_id,
+                duration,
+                auto_relend: relend.map(|(cycles, with_interest)| AutoRelend { cycles, with_interest }),
+            }),
+            (id(), id()).prop_map(|(collection_id, offer_id)| ExecuteMsg::StopAutoRelend { collection_id, offer_id }),
+            prop::collection::vec((id(), duration(), amount()), 0..4).prop_map(|items| ExecuteMsg::BatchLend {
+                items: items
+                    .into_iter()
+                    .map(|(collection_id, duration, amount)| LendItem { collection_id, duration, amount, auto_relend: None })
+                    .collect(),
+            }),
+            prop::collection::vec((id(), id(), amount()), 0..4).prop_map(|items| ExecuteMsg::BatchRepay {
+                items: items.into_iter().map(|(collection_id, offer_id, amount)| RepayItem { collection_id, offer_id, amount }).collect(),
+            }),
+            prop::collection::vec((id(), id()), 0..4).prop_map(|offers| ExecuteMsg::BatchWithdraw {
+                offers: offers.into_iter().map(|(collection_id, offer_id)| OfferRef { collection_id, offer_id }).collect(),
+            }),
+            prop::collection::vec((id(), id()), 0..4).prop_map(|offers| ExecuteMsg::BatchClaim {
+                offers: offers.into_iter().map(|(collection_id, offer_id)| OfferRef { collection_id, offer_id }).collect(),
+            }),
+            (id(), id(), prop::option::of(amount()), prop::option::of(duration()), prop::option::of(any::<u32>())).prop_map(
+                |(collection_id, offer_id, new_amount, new_duration, new_interest)| ExecuteMsg::UpdateOffer {
+                    collection_id,
+                    offer_id,
+                    new_amount,
+                    new_duration,
+                    new_interest,
+                },
+            ),
+            (id(), token_id()).prop_map(|(collection_id, token_id)| ExecuteMsg::Borrow { collection_id, token_id }),
+            (id(), token_id(), prop_oneof![
+                prop::collection::vec(id(), 0..4).prop_map(Fill::OfferIds),
+                any::<u128>().prop_map(|a| Fill::TargetAmount(Uint128::from(a % 100_000_000))),
+            ]).prop_map(|(collection_id, token_id, fill)| ExecuteMsg::BorrowAggregated { collection_id, token_id, fill }),
+            (id(), prop::collection::vec((id(), token_id()), 0..4)).prop_map(|(collection_id, tokens)| ExecuteMsg::BorrowBundle {
+                collection_id,
+                tokens: tokens.into_iter().map(|(collection_id, token_id)| BundleToken { collection_id, token_id }).collect(),
+            }),
+            (id(), token_id(), prop_oneof![0_u32..6, any::<u32>()]).prop_map(|(collection_id, token_id, installments)| ExecuteMsg::BorrowInstallments {
+                collection_id,
+                token_id,
+                installments,
+            }),
+            (id(), id()).prop_map(|(collection_id, offer_id)| ExecuteMsg::PayInstallment { collection_id, offer_id }),
+            (id(), id()).prop_map(|(collection_id, offer_id)| ExecuteMsg::Repay { collection_id, offer_id }),
+            (id(), id()).prop_map(|(collection_id, offer_id)| ExecuteMsg::Claim { collection_id, offer_id }),
+            (id(), id()).prop_map(|(collection_id, offer_id)| ExecuteMsg::Withdraw { collection_id, offer_id }),
+            prop::option::of(prop::sample::subsequence(Operation::ALL.to_vec(), 0..3))
+                .prop_map(|operations| ExecuteMsg::Pause { operations }),
+            prop::option::of(prop::sample::subsequence(Operation::ALL.to_vec(), 0..3))
+                .prop_map(|operations| ExecuteMsg::Unpause { operations }),
+            any::<bool>().prop_map(|enabled| ExecuteMsg::SetEmergency { enabled }),
+            (0_u16..6000, prop::option::of(0_u16..6000)).prop_map(|(founder_fee_bps, penalty_fee_bps)| ExecuteMsg::UpdateFees { founder_fee_bps, penalty_fee_bps }),
+            (id(), prop::option::of((any::<u64>(), any::<u32>()))).prop_map(|(collection_id, penalty)| ExecuteMsg::SetLatePenalty {
+                collection_id,
+                penalty: penalty.map(|(window, apr_bps)| LatePenalty { window: window % 1_000_000, apr_bps }),
+            }),
+        ]
+    }
+
+    fn step() -> impl Strategy<Value = (ExecuteMsg, usize, Vec<Coin>, u64)> {
+        (execute_msg(), 0..SENDERS.len(), funds(), 0_u64..100_000)
+    }
+
+    proptest! {
+        #[test]
+        fn messages_never_panic(
+            steps in prop::collection::vec(step(), 1..40),
+            ttl in prop_oneof![Just(0_u64), 1_u64..200_000, Just(u64::MAX)],
+            batch in 0_u32..5,
+        ) {
+            let mut deps = mock_dependencies();
+            deps.querier.update_wasm(mock_collections);
+            let instantiate_msg = InstantiateMsg {
+                founder_one: "foun1".to_string(),
+                founder_two: "foun2".to_string(),
+                guardian: Some("guardian".to_string()),
+            };
+            instantiate(deps.as_mut(), mock_env(), mock_info("owner", &[]), instantiate_msg).unwrap();
+            let update = SudoMsg::UpdateParams { founder_fee_bps: None, offer_ttl: Some(ttl), keeper_batch_size: Some(batch), allowed_code_ids: None };
+            sudo(deps.as_mut(), mock_env(), update).unwrap();
+
+            let mut env = mock_env();
+            for (msg, sender, funds, elapsed) in steps {
+                env.block.time = env.block.time.plus_seconds(elapsed);
+                // errors are fine, aborting the wasm is not
+                let _ = execute(deps.as_mut(), env.clone(), mock_info(SENDERS[sender], &funds), msg);
+                let _ = sudo(deps.as_mut(), env.clone(), SudoMsg::BeginBlocker {});
+            }
+
+            let collections: Vec<Collection> =
+                from_json(query(deps.as_ref(), env.clone(), QueryMsg::CollectionsList {}).unwrap()).unwrap();
+            for i in 0..collections.len() + 1 {
+                for from in SENDERS.iter().chain(["", "UPPER"].iter()) {
+                    let collection_id = Uint128::from(i as u128);
+                    let _ = query(deps.as_ref(), env.clone(), QueryMsg::GetBestOffer { collection_id, from: from.to_string() });
+                    let _ = query(deps.as_ref(), env.clone(), QueryMsg::GetOffers { collection_id, from: from.to_string() });
+                    let _ = query(deps.as_ref(), env.clone(), QueryMsg::GetCollectionData { collection_id, from: from.to_string() });
+                }
+                for from in SENDERS.iter() {
+                    let _ = query(deps.as_ref(), env.clone(), QueryMsg::Portfolio { address: from.to_string() });
+                }
+                let _ = query(deps.as_ref(), env.clone(), QueryMsg::CollectionStats { collection_id: Uint128::from(i as u128) });
+                let _ = query(deps.as_ref(), env.clone(), QueryMsg::LoansExpiring {
+                    from: mock_env().block.time,
+                    to: env.block.time.plus_seconds(200_000),
+                    collection_id: Some(Uint128::from(i as u128)),
+                    limit: None,
+                    start_after: Some(OfferRef { collection_id: Uint128::from(i as u128), offer_id: Uint128::zero() }),
+                });
+                for offer_id in 0..4_u128 {
+                    let _ = query(deps.as_ref(), env.clone(), QueryMsg::LoanSchedule { collection_id: Uint128::from(i as u128), offer_id: Uint128::from(offer_id) });
+                }
+            }
+
+            let indexed = MATURITIES.keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+            let open = OPEN_OFFERS.keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+            let due = GRACE_ENDS.keys(deps.as_ref().storage, None, None, Order::Ascending).count();
+            let mut outstanding = 0;
+            let mut listed = 0;
+            let mut taken = 0;
+            for (id, collection) in collections.into_iter().enumerate() {
+                let offers = OFFERS.load(deps.as_ref().storage, collection.addr).unwrap();
+                let mut replayed = CollectionStats::default();
+                for offer in offers.iter() {
+                    replay(&mut replayed, offer).unwrap();
+                }
+                prop_assert_eq!(STATS.may_load(deps.as_ref().storage, id as u128).unwrap().unwrap_or_default(), replayed);
+                outstanding += offers.iter().filter(|o| o.in_escrow()).count();
+                listed += offers.iter().filter(|o| o.status == Status::Open).count();
+                taken += offers.iter().filter(|o| o.status == Status::Taken).count();
+                for (i, offer) in offers.iter().enumerate() {
+                    prop_assert_eq!(Uint128::from(i as u128), offer.offer_id);
+                    prop_assert_eq!(Some(&offer.status), offer.history.last().map(|t| &t.status));
+                }
+            }
+            prop_assert_eq!(outstanding, indexed);
+            prop_assert_eq!(listed, open);
+            prop_assert_eq!(taken, due);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use cosmwasm_std::{Timestamp, Uint128, StdError, Addr};
+use crate::state::{Operation, Role, Status, TokenStandard};
 use cw721_base::ContractError as Cw721ContractError;
 use thiserror::Error;
 use cw_utils::PaymentError;
@@ -17,6 +18,24 @@ pub enum ContractError {
     #[error("Not authorized")]
     NotOwner {},
 
+    #[error("Missing role {role}")]
+    Unauthorized {
+        role: Role
+    },
+
+    #[error("Cannot revoke the last admin")]
+    LastAdmin {},
+
+    #[error("Founder fee of {founder_fee_bps} bps is too high")]
+    InvalidFee {
+        founder_fee_bps: u16
+    },
+
+    #[error("{operation} is paused")]
+    Paused {
+        operation: Operation
+    },
+
     #[error("Only the borrower can repay {borrower}")]
     NotBorrower {
         borrower: Addr
@@ -25,6 +44,65 @@ pub enum ContractError {
     #[error("Only the lender can claim")]
     NotLender {},
 
+    #[error("Can't migrate from {contract}")]
+    InvalidMigration {
+        contract: String
+    },
+
+    #[error("{address} is not a cw721 contract")]
+    NotCw721 {
+        address: Addr
+    },
+
+    #[error("{address} is not a cw1155 contract")]
+    NotCw1155 {
+        address: Addr
+    },
+
+    #[error("Collection uses {standard}")]
+    WrongStandard {
+        standard: TokenStandard
+    },
+
+    #[error("Collateral can only be sent by {collection}")]
+    NotCollection {
+        collection: Addr
+    },
+
+    #[error("A bundle needs a token from the lending collection and no duplicates")]
+    InvalidBundle {},
+
+    #[error("Only {amount} of the target could be filled")]
+    TargetNotFilled {
+        amount: Uint128
+    },
+
+    #[error("Only the senior lender can claim")]
+    NotSeniorLender {},
+
+    #[error("Collateral must be sent by its owner")]
+    NoCollateralOwner {},
+
+    #[error("Code id {code_id} is not allowed")]
+    CodeIdNotAllowed {
+        code_id: u64
+    },
+
+    #[error("Collection {address} is already listed")]
+    CollectionExists {
+        address: Addr
+    },
+
+    #[error("{address} is not allowed to use this collection")]
+    NotAllowed {
+        address: Addr
+    },
+
+    #[error("Token {token_id} is blocked")]
+    TokenBlocked {
+        token_id: String
+    },
+
     #[error("Lender cannot borrow")]
     IsLender {},
 
@@ -36,6 +114,19 @@ pub enum ContractError {
         collection_id: Uint128
     },
 
+    #[error("Offer {offer_id} doesn't exist")]
+    InvalidOfferId {
+        offer_id: Uint128
+    },
+
+    #[error("Invalid loan duration {duration}")]
+    InvalidDuration {
+        duration: Uint128
+    },
+
+    #[error("Interest calculation overflowed")]
+    InterestOverflow {},
+
     #[error("The loan is not taken")]
     NotTaken {},
 
@@ -50,6 +141,22 @@ pub enum ContractError {
         amount: Uint128
     },
 
+    #[error("Batch of {size} items, expected 1 to {max}")]
+    BatchSize {
+        size: usize,
+        max: usize,
+    },
+
+    #[error("Expected {expected}{denom} for the batch, received {received}{denom}")]
+    FundsMismatch {
+        denom: String,
+        expected: Uint128,
+        received: Uint128,
+    },
+
+    #[error("Offer amount must be positive, withdraw the offer instead")]
+    InvalidAmount {},
+
     #[error("The offer is not paid")]
     NotPaid {},
 
@@ -63,6 +170,42 @@ pub enum ContractError {
         active_till: Timestamp,
     },
 
+    #[error("Penalty fee of {penalty_fee_bps} bps is too high")]
+    InvalidPenaltyFee {
+        penalty_fee_bps: u16,
+    },
+
+    #[error("Can't split the loan into {installments} installments")]
+    InvalidInstallments {
+        installments: u32,
+    },
+
+    #[error("The loan has no installment schedule")]
+    NoSchedule {},
+
     #[error("Active till missing")]
-    ActiveTillMissing {}
+    ActiveTillMissing {},
+
+    #[error("Loan of {amount} exceeds the collateral limit of {max}")]
+    LtvExceeded {
+        amount: Uint128,
+        max: Uint128,
+    },
+
+    #[error("APY must be between 1 and {max} bps, got {apy_bps}")]
+    InvalidApy {
+        apy_bps: u32,
+        max: u32,
+    },
+
+    #[error("Max LTV must be at most 10000 bps, got {max_ltv_bps}")]
+    InvalidLtv {
+        max_ltv_bps: u16
+    },
+
+    #[error("Offer cannot move from {from} to {to}")]
+    InvalidTransition {
+        from: Status,
+        to: Status,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,258 @@
+use cosmwasm_std::{Decimal256, Timestamp, Uint128};
+
+use crate::error::ContractError;
+use crate::state::{InterestMode, BPS_DENOMINATOR};
+
+// Interest is computed on Decimal256 (18 decimal places) without floats, so
+// every node arrives at the same result. Intermediate results are truncated to
+// 18 decimals and the final interest is rounded to the nearest whole unit,
+// halves up.
+
+pub const SECONDS_PER_YEAR: u128 = 31_536_000;
+
+const LN_2: Decimal256 = Decimal256::raw(693_147_180_559_945_309);
+
+/// Interest owed on `amount` lent for `duration` seconds at the yearly `rate`
+/// (`0.1` = 10%), accrued according to `mode`.
+pub fn interest(
+    mode: InterestMode,
+    rate: Decimal256,
+    duration: Uint128,
+    amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    if duration.is_zero() {
+        return Err(ContractError::InvalidDuration { duration });
+    }
+    checked_interest(mode, rate, duration, amount).ok_or(ContractError::InterestOverflow {})
+}
+
+fn checked_interest(
+    mode: InterestMode,
+    rate: Decimal256,
+    duration: Uint128,
+    amount: Uint128,
+) -> Option<Uint128> {
+    let years = Decimal256::checked_from_ratio(duration, SECONDS_PER_YEAR).ok()?;
+    let growth = match mode {
+        InterestMode::Simple => rate.checked_mul(years).ok()?,
+        InterestMode::Compound => compound_factor(rate, duration)?.checked_sub(Decimal256::one()).ok()?,
+        InterestMode::Continuous => exp(rate.checked_mul(years).ok()?)?.checked_sub(Decimal256::one()).ok()?,
+    };
+    let interest = growth
+        .checked_mul(Decimal256::from_atomics(amount, 0).ok()?)
+        .ok()?
+        .checked_add(Decimal256::percent(50))
+        .ok()?
+        .to_uint_floor();
+    Uint128::try_from(interest).ok()
+}
+
+/// (1 + rate) ^ (duration / year), whole years by repeated multiplication and
+/// the remainder through exp and ln.
+fn compound_factor(rate: Decimal256, duration: Uint128) -> Option<Decimal256> {
+    let base = Decimal256::one().checked_add(rate).ok()?;
+    let whole_years = u32::try_from(duration.u128() / SECONDS_PER_YEAR).ok()?;
+    let remainder = Decimal256::from_ratio(duration.u128() % SECONDS_PER_YEAR, SECONDS_PER_YEAR);
+    let fraction = exp(ln(base)?.checked_mul(remainder).ok()?)?;
+    base.checked_pow(whole_years).ok()?.checked_mul(fraction).ok()
+}
+
+/// Natural logarithm of `x >= 1`.
+fn ln(x: Decimal256) -> Option<Decimal256> {
+    // x = 2^k * m with 1 <= m < 2, ln(x) = k * ln(2) + ln(m)
+    let two = Decimal256::from_ratio(2_u8, 1_u8);
+    let mut k = 0_u32;
+    let mut m = x;
+    while m >= two {
+        m = m.checked_div(two).ok()?;
+        k += 1;
+    }
+    // ln(m) = 2 * atanh(z) = 2 * (z + z^3/3 + z^5/5 + ...) with z = (m - 1) / (m + 1) <= 1/3
+    let z = m
+        .checked_sub(Decimal256::one())
+        .ok()?
+        .checked_div(m.checked_add(Decimal256::one()).ok()?)
+        .ok()?;
+    let z2 = z.checked_mul(z).ok()?;
+    let mut term = z;
+    let mut sum = Decimal256::zero();
+    let mut n = 1_u32;
+    while !term.is_zero() {
+        sum = sum.checked_add(term.checked_div(Decimal256::from_ratio(n, 1_u8)).ok()?).ok()?;
+        term = term.checked_mul(z2).ok()?;
+        n += 2;
+    }
+    LN_2
+        .checked_mul(Decimal256::from_ratio(k, 1_u8))
+        .ok()?
+        .checked_add(sum.checked_mul(two).ok()?)
+        .ok()
+}
+
+/// e^y for `y >= 0`, `None` when the result overflows.
+fn exp(y: Decimal256) -> Option<Decimal256> {
+    // halve until y <= 1/2, then e^y = (e^(y / 2^k))^(2^k)
+    let two = Decimal256::from_ratio(2_u8, 1_u8);
+    let mut k = 0_u32;
+    let mut r = y;
+    while r > Decimal256::percent(50) {
+        r = r.checked_div(two).ok()?;
+        k += 1;
+    }
+    let mut sum = Decimal256::one();
+    let mut term = Decimal256::one();
+    let mut n = 1_u32;
+    loop {
+        term = term.checked_mul(r).ok()?.checked_div(Decimal256::from_ratio(n, 1_u8)).ok()?;
+        if term.is_zero() {
+            break;
+        }
+        sum = sum.checked_add(term).ok()?;
+        n += 1;
+    }
+    for _ in 0..k {
+        sum = sum.checked_mul(sum).ok()?;
+    }
+    Some(sum)
+}
+
+/// Yearly rate of `bps` basis points (`750` = 7.5%).
+pub fn bps_rate(bps: u32) -> Decimal256 {
+    Decimal256::from_ratio(bps, BPS_DENOMINATOR)
+}
+
+/// Penalty on `principal` overdue since `due`, accrued per second at
+/// `apr_bps` basis points a year.
+pub fn late_penalty(apr_bps: u32, principal: Uint128, due: Timestamp, now: Timestamp) -> Result<Uint128, ContractError> {
+    let late = now.seconds().saturating_sub(due.seconds());
+    if late == 0 {
+        return Ok(Uint128::zero());
+    }
+    interest(InterestMode::Simple, bps_rate(apr_bps), Uint128::from(late), principal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u128 = 86400;
+    const YEAR: u128 = SECONDS_PER_YEAR;
+
+    fn rate(percent: u64) -> Decimal256 {
+        Decimal256::percent(percent)
+    }
+
+    fn calc(mode: InterestMode, percent: u64, duration: u128, amount: u128) -> u128 {
+        interest(mode, rate(percent), Uint128::from(duration), Uint128::from(amount)).unwrap().u128()
+    }
+
+    #[test]
+    fn simple_apr () {
+        assert_eq!(50, calc(InterestMode::Simple, 10, YEAR / 2, 1000));
+        // 24.657...
+        assert_eq!(25, calc(InterestMode::Simple, 90, DAY, 10000));
+        assert_eq!(0, calc(InterestMode::Simple, 0, DAY, 10000));
+    }
+
+    #[test]
+    fn compound_apy () {
+        // 10000 * (1.9^(1/365) - 1) = 17.6005...
+        assert_eq!(18, calc(InterestMode::Compound, 90, DAY, 10000));
+        // sqrt(1.21) = 1.1 exactly, truncation must not round it down to 99
+        assert_eq!(100, calc(InterestMode::Compound, 21, YEAR / 2, 1000));
+        assert_eq!(2100, calc(InterestMode::Compound, 10, 2 * YEAR, 10000));
+        // 10^24 * (1.1^3.25 - 1) = 3.6309532017139646723e23
+        let big = calc(InterestMode::Compound, 10, 3 * YEAR + YEAR / 4, 10_u128.pow(24));
+        assert!(big.abs_diff(363_095_320_171_396_467_236_917) < 10_u128.pow(8), "{}", big);
+    }
+
+    #[test]
+    fn continuous_accrual () {
+        // 10^6 * (e^0.1 - 1) = 105170.918...
+        assert_eq!(105171, calc(InterestMode::Continuous, 10, YEAR, 1_000_000));
+        // 10000 * (e^(0.9 / 365) - 1) = 24.6879...
+        assert_eq!(25, calc(InterestMode::Continuous, 90, DAY, 10000));
+        // 10^6 * (e^10 - 1) = 22025465794.806...
+        let big = calc(InterestMode::Continuous, 500, 2 * YEAR, 1_000_000);
+        assert!(big.abs_diff(22_025_465_795) < 10, "{}", big);
+    }
+
+    #[test]
+    fn modes_order () {
+        // for the same nominal rate: simple < compound yearly < continuous beyond one year
+        let simple = calc(InterestMode::Simple, 20, 3 * YEAR, 1_000_000);
+        let compound = calc(InterestMode::Compound, 20, 3 * YEAR, 1_000_000);
+        let continuous = calc(InterestMode::Continuous, 20, 3 * YEAR, 1_000_000);
+        assert_eq!(600_000, simple);
+        assert_eq!(728_000, compound);
+        assert!(simple < compound && compound < continuous);
+    }
+
+    #[test]
+    fn invalid_inputs () {
+        for mode in [InterestMode::Simple, InterestMode::Compound, InterestMode::Continuous] {
+            assert_eq!(
+                ContractError::InvalidDuration { duration: Uint128::zero() },
+                interest(mode, rate(10), Uint128::zero(), Uint128::from(1000_u128)).unwrap_err()
+            );
+            assert_eq!(
+                ContractError::InterestOverflow {},
+                interest(mode, rate(1000), Uint128::MAX, Uint128::MAX).unwrap_err()
+            );
+        }
+        assert_eq!(
+            ContractError::InterestOverflow {},
+            interest(InterestMode::Continuous, Decimal256::MAX, Uint128::from(YEAR), Uint128::one()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn bps_rates () {
+        assert_eq!(rate(90), bps_rate(9000));
+        assert_eq!(Decimal256::permille(75), bps_rate(750));
+        assert_eq!(Decimal256::from_ratio(1225_u32, 10000_u32), bps_rate(1225));
+    }
+
+    #[test]
+    fn late_penalties () {
+        let due = Timestamp::from_seconds(1000);
+        let principal = Uint128::from(1_000_000_u128);
+        assert_eq!(Uint128::zero(), late_penalty(3650, principal, due, due).unwrap());
+        assert_eq!(Uint128::zero(), late_penalty(3650, principal, due, Timestamp::from_seconds(10)).unwrap());
+        // 36.5% a year is 0.1% a day
+        assert_eq!(Uint128::from(1000_u128), late_penalty(3650, principal, due, due.plus_seconds(DAY as u64)).unwrap());
+        assert_eq!(Uint128::from(500_u128), late_penalty(3650, principal, due, due.plus_seconds(DAY as u64 / 2)).unwrap());
+    }
+
+    /// cosmwasm rejects contracts with float instructions. Builds the contract
+    /// for wasm in its own target directory and checks the output, so it needs
+    /// the `wasm32-unknown-unknown` target installed.
+    #[test]
+    fn wasm_has_no_float_ops () {
+        use wasmparser::{Parser, Payload};
+
+        let manifest = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let target_dir = manifest.join("../target/wasm-check");
+        let status = std::process::Command::new(env!("CARGO"))
+            .current_dir(manifest)
+            .args(["build", "--release", "--lib", "--target", "wasm32-unknown-unknown", "--target-dir"])
+            .arg(&target_dir)
+            .status()
+            .expect("cargo runs");
+        assert!(status.success(), "building the wasm failed, is wasm32-unknown-unknown installed?");
+        let path = target_dir.join("wasm32-unknown-unknown/release/injective_bank.wasm");
+        let wasm = std::fs::read(&path).unwrap_or_else(|e| panic!("{} not built: {}", path.display(), e));
+        let mut floats = vec![];
+        for payload in Parser::new(0).parse_all(&wasm) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                for op in body.get_operators_reader().unwrap() {
+                    let op = format!("{:?}", op.unwrap());
+                    if op.starts_with("F32") || op.starts_with("F64") {
+                        floats.push(op);
+                    }
+                }
+            }
+        }
+        assert!(floats.is_empty(), "float operations in wasm: {:?}", floats);
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{coins, BankMsg, DepsMut, Env, Order, Response, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::contract::offer_event;
+use crate::error::ContractError;
+use crate::lifecycle::transition;
+use crate::stats::record_default;
+use crate::state::{
+    Offer, Operation, Status, COLLECTIONS, GRACE_ENDS, OFFERS, OPEN_OFFERS, PARAMS, PAUSE,
+};
+
+type IndexKey = (u64, u128, u128);
+
+/// Marks loans past their grace period as defaulted and expires stale open
+/// offers.
+///
+/// Due offers are looked up in `GRACE_ENDS` and `OPEN_OFFERS`, so only the
+/// offers of collections with something due are loaded. At most
+/// `keeper_batch_size` offers are handled per call, defaults before expiries
+/// and the oldest first; the rest stay indexed for the next block.
+/// Nothing runs in emergency mode. Expiry refunds wait while withdrawals are
+/// paused and defaults while repayments are, as borrowers can't repay then.
+pub fn begin_blocker(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let params = PARAMS.may_load(deps.storage)?.unwrap_or_default();
+    let pause = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    let mut response = Response::new();
+    if params.keeper_batch_size == 0 || pause.emergency {
+        return Ok(response);
+    }
+    let now = env.block.time;
+    let budget = params.keeper_batch_size as usize;
+
+    let mut due: Vec<(IndexKey, &'static str)> = vec![];
+    if pause.allows(Operation::Repay) {
+        let max = Bound::inclusive((now.nanos(), u128::MAX, u128::MAX));
+        for key in GRACE_ENDS.keys(deps.storage, None, Some(max), Order::Ascending).take(budget) {
+            due.push((key?, "default"));
+        }
+    }
+    if params.offer_ttl > 0 && pause.allows(Operation::Withdraw) {
+        // offers listed `offer_ttl` seconds ago or earlier have expired
+        let listed_by = params.offer_ttl
+            .checked_mul(1_000_000_000)
+            .and_then(|ttl| now.nanos().checked_sub(ttl));
+        if let Some(listed_by) = listed_by {
+            let max = Bound::inclusive((listed_by, u128::MAX, u128::MAX));
+            for key in OPEN_OFFERS.keys(deps.storage, None, Some(max), Order::Ascending).take(budget - due.len()) {
+                due.push((key?, "expire"));
+            }
+        }
+    }
+    if due.is_empty() {
+        return Ok(response.add_attribute("action", "begin_blocker"));
+    }
+
+    let mut by_collection: BTreeMap<u128, Vec<(IndexKey, &'static str)>> = BTreeMap::new();
+    for (key, action) in due {
+        by_collection.entry(key.1).or_default().push((key, action));
+    }
+    let collections = COLLECTIONS.load(deps.storage)?;
+    for (collection_id, due) in by_collection {
+        let collection = &collections[collection_id as usize];
+        let collection_id = Uint128::from(collection_id);
+        let mut offers = OFFERS.load(deps.storage, collection.addr.clone())?;
+        for (key, action) in due {
+            if action == "default" {
+                GRACE_ENDS.remove(deps.storage, key);
+            } else {
+                OPEN_OFFERS.remove(deps.storage, key);
+            }
+            let offer = &mut offers[key.2 as usize];
+            if !process_offer(offer, action, now)? {
+                continue;
+            }
+            if action == "default" {
+                record_default(deps.storage, collection_id)?;
+            }
+            if action == "expire" {
+                response = response.add_message(BankMsg::Send {
+                    to_address: offer.lender.to_string(),
+                    amount: coins(offer.amount.u128(), "inj"),
+                });
+            }
+            response = response.add_event(offer_event(action, collection_id, offer, Uint128::zero()));
+        }
+        OFFERS.save(deps.storage, collection.addr.clone(), &offers)?;
+    }
+
+    Ok(response.add_attribute("action", "begin_blocker"))
+}
+
+/// Applies `action` to `offer`, unless the offer has moved on since it was indexed.
+fn process_offer(offer: &mut Offer, action: &str, now: Timestamp) -> Result<bool, ContractError> {
+    match (action, &offer.status) {
+        ("default", Status::Taken) => transition(offer, Status::Defaulted, now)?,
+        ("expire", Status::Open) => transition(offer, Status::Expired, now)?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
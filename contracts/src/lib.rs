@@ -1,6 +1,16 @@
+pub mod access;
+pub mod batch;
+pub mod collateral;
 pub mod contract;
+pub mod interest;
 mod error;
+pub mod keeper;
+pub mod lifecycle;
+pub mod listing;
 pub mod msg;
+pub mod oracle;
+pub mod schedule;
 pub mod state;
+pub mod stats;
 
 pub use crate::error::ContractError;
\ No newline at end of file
@@ -0,0 +1,152 @@
+use cosmwasm_std::Timestamp;
+
+use crate::error::ContractError;
+use crate::state::{Offer, Status, Transition};
+
+// Loan lifecycle:
+//
+//   Open ──> Taken ──> Paid ──────> Closed
+//    │  │          └─> Defaulted ──┘
+//    │  └─> Cancelled
+//    └────> Expired
+//
+// Cancelled, Expired and Closed are terminal.
+
+/// Returns whether an offer may move from `from` to `to`.
+pub fn can_transition(from: &Status, to: &Status) -> bool {
+    use Status::*;
+    matches!(
+        (from, to),
+        (Open, Taken)
+            | (Open, Cancelled)
+            | (Open, Expired)
+            | (Taken, Paid)
+            | (Taken, Defaulted)
+            | (Paid, Closed)
+            | (Defaulted, Closed)
+    )
+}
+
+/// Moves `offer` to `to`, recording the block time of the change.
+/// Every handler changes offer status through this function.
+pub fn transition(offer: &mut Offer, to: Status, at: Timestamp) -> Result<(), ContractError> {
+    if !can_transition(&offer.status, &to) {
+        return Err(ContractError::InvalidTransition {
+            from: offer.status.clone(),
+            to,
+        });
+    }
+    offer.history.push(Transition {
+        status: to.clone(),
+        at,
+    });
+    offer.status = to;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Uint128};
+
+    use super::*;
+
+    const ALL: [Status; 7] = [
+        Status::Open,
+        Status::Taken,
+        Status::Paid,
+        Status::Defaulted,
+        Status::Closed,
+        Status::Cancelled,
+        Status::Expired,
+    ];
+
+    fn offer(status: Status) -> Offer {
+        Offer {
+            offer_id: Uint128::zero(),
+            amount: Uint128::from(100_u128),
+            active_till: Timestamp::from_nanos(0),
+            interest: Uint128::from(1_u128),
+            apy_bps: 9000,
+            lender: Addr::unchecked("lender"),
+            borrower: None,
+            token_id: None,
+            token_amount: None,
+            bundle: vec![],
+            syndicate: vec![],
+            schedule: vec![],
+            late_penalty: None,
+            auto_relend: None,
+            status,
+            duration: Uint128::from(60_u128),
+            history: vec![],
+        }
+    }
+
+    #[test]
+    fn allowed_transitions () {
+        let allowed = [
+            (Status::Open, Status::Taken),
+            (Status::Open, Status::Cancelled),
+            (Status::Open, Status::Expired),
+            (Status::Taken, Status::Paid),
+            (Status::Taken, Status::Defaulted),
+            (Status::Paid, Status::Closed),
+            (Status::Defaulted, Status::Closed),
+        ];
+        for from in ALL.iter() {
+            for to in ALL.iter() {
+                let expected = allowed.contains(&(from.clone(), to.clone()));
+                assert_eq!(expected, can_transition(from, to), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_leave_offer_untouched () {
+        for from in ALL.iter() {
+            for to in ALL.iter() {
+                if can_transition(from, to) {
+                    continue;
+                }
+                let mut o = offer(from.clone());
+                let err = transition(&mut o, to.clone(), Timestamp::from_seconds(5)).unwrap_err();
+                assert_eq!(ContractError::InvalidTransition { from: from.clone(), to: to.clone() }, err);
+                assert_eq!(offer(from.clone()), o);
+            }
+        }
+    }
+
+    #[test]
+    fn terminal_states () {
+        for from in [Status::Closed, Status::Cancelled, Status::Expired].iter() {
+            assert!(ALL.iter().all(|to| !can_transition(from, to)));
+        }
+    }
+
+    #[test]
+    fn records_each_transition () {
+        let mut o = offer(Status::Open);
+        transition(&mut o, Status::Taken, Timestamp::from_seconds(10)).unwrap();
+        transition(&mut o, Status::Defaulted, Timestamp::from_seconds(20)).unwrap();
+        transition(&mut o, Status::Closed, Timestamp::from_seconds(30)).unwrap();
+        assert_eq!(Status::Closed, o.status);
+        assert_eq!(
+            vec![
+                Transition { status: Status::Taken, at: Timestamp::from_seconds(10) },
+                Transition { status: Status::Defaulted, at: Timestamp::from_seconds(20) },
+                Transition { status: Status::Closed, at: Timestamp::from_seconds(30) },
+            ],
+            o.history
+        );
+        assert_eq!(Some(Timestamp::from_seconds(20)), o.transitioned_at(&Status::Defaulted));
+        assert_eq!(None, o.transitioned_at(&Status::Paid));
+    }
+
+    #[test]
+    fn legacy_status_names () {
+        let status: Status = cosmwasm_std::from_json(br#""failed""#).unwrap();
+        assert_eq!(Status::Defaulted, status);
+        let status: Status = cosmwasm_std::from_json(br#""done""#).unwrap();
+        assert_eq!(Status::Closed, status);
+    }
+}
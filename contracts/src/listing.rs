@@ -0,0 +1,129 @@
+use cosmwasm_std::{Addr, Deps};
+use cw721::{ContractInfoResponse, Cw721QueryMsg, NumTokensResponse};
+
+use crate::error::ContractError;
+use crate::msg::{Cw1155QueryMsg, Cw1155TokensResponse};
+use crate::state::{Params, TokenStandard};
+
+/// Checks that `collection` answers the queries of its token standard and,
+/// when `params` restricts code ids, that it was instantiated from one of them.
+///
+/// Returns the collection's cw721 name and symbol, cw1155 has neither.
+pub fn verify_collection(
+    deps: Deps,
+    collection: &Addr,
+    standard: TokenStandard,
+    params: &Params,
+) -> Result<ContractInfoResponse, ContractError> {
+    if !params.allowed_code_ids.is_empty() {
+        let info = deps.querier.query_wasm_contract_info(collection)?;
+        if !params.allowed_code_ids.contains(&info.code_id) {
+            return Err(ContractError::CodeIdNotAllowed { code_id: info.code_id });
+        }
+    }
+
+    match standard {
+        TokenStandard::Cw721 => verify_cw721(deps, collection),
+        TokenStandard::Cw1155 => {
+            let _: Cw1155TokensResponse = deps
+                .querier
+                .query_wasm_smart(collection, &Cw1155QueryMsg::AllTokens { start_after: None, limit: Some(1) })
+                .map_err(|_| ContractError::NotCw1155 { address: collection.clone() })?;
+            Ok(ContractInfoResponse { name: String::new(), symbol: String::new() })
+        }
+    }
+}
+
+fn verify_cw721(deps: Deps, collection: &Addr) -> Result<ContractInfoResponse, ContractError> {
+    let not_cw721 = |_| ContractError::NotCw721 { address: collection.clone() };
+    let info: ContractInfoResponse = deps
+        .querier
+        .query_wasm_smart(collection, &Cw721QueryMsg::ContractInfo {})
+        .map_err(not_cw721)?;
+    let _: NumTokensResponse = deps
+        .querier
+        .query_wasm_smart(collection, &Cw721QueryMsg::NumTokens {})
+        .map_err(not_cw721)?;
+    Ok(info)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::{
+        from_json, to_json_binary, ContractInfoResponse as WasmContractInfo, ContractResult,
+        QuerierResult, SystemError, SystemResult, WasmQuery,
+    };
+
+    use super::*;
+
+    /// Stands in for listed collections: addresses starting with `coll` answer
+    /// cw721 queries, those starting with `semi` answer cw1155 queries, and all
+    /// of them were instantiated from code id 1.
+    pub(crate) fn mock_collections(query: &WasmQuery) -> QuerierResult {
+        match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr.starts_with("semi") => {
+                let res = match from_json(msg) {
+                    Ok(Cw1155QueryMsg::AllTokens { .. }) => {
+                        to_json_binary(&Cw1155TokensResponse { tokens: vec!["gold".to_string()] })
+                    }
+                    Err(e) => return SystemResult::Ok(ContractResult::Err(e.to_string())),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            WasmQuery::Smart { contract_addr, msg } if contract_addr.starts_with("coll") => {
+                let res = match from_json(msg) {
+                    Ok(Cw721QueryMsg::ContractInfo {}) => to_json_binary(&ContractInfoResponse {
+                        name: format!("{} name", contract_addr),
+                        symbol: contract_addr.to_uppercase(),
+                    }),
+                    Ok(Cw721QueryMsg::NumTokens {}) => to_json_binary(&NumTokensResponse { count: 3 }),
+                    _ => return SystemResult::Ok(ContractResult::Err("unsupported query".to_string())),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            WasmQuery::ContractInfo { contract_addr } if contract_addr.starts_with("coll") || contract_addr.starts_with("semi") => {
+                let mut info = WasmContractInfo::default();
+                info.code_id = 1;
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            WasmQuery::Smart { contract_addr, .. } | WasmQuery::ContractInfo { contract_addr } => {
+                SystemResult::Err(SystemError::NoSuchContract { addr: contract_addr.clone() })
+            }
+            _ => panic!("unexpected query"),
+        }
+    }
+
+    #[test]
+    fn reads_cw721_info () {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+        let info = verify_collection(deps.as_ref(), &Addr::unchecked("coll_one"), TokenStandard::Cw721, &Params::default()).unwrap();
+        assert_eq!("coll_one name", info.name);
+        assert_eq!("COLL_ONE", info.symbol);
+        let info = verify_collection(deps.as_ref(), &Addr::unchecked("semi_one"), TokenStandard::Cw1155, &Params::default()).unwrap();
+        assert_eq!("", info.name);
+    }
+
+    #[test]
+    fn rejects_other_contracts () {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+        let err = verify_collection(deps.as_ref(), &Addr::unchecked("token"), TokenStandard::Cw721, &Params::default()).unwrap_err();
+        assert_eq!(ContractError::NotCw721 { address: Addr::unchecked("token") }, err);
+        let err = verify_collection(deps.as_ref(), &Addr::unchecked("coll_one"), TokenStandard::Cw1155, &Params::default()).unwrap_err();
+        assert_eq!(ContractError::NotCw1155 { address: Addr::unchecked("coll_one") }, err);
+    }
+
+    #[test]
+    fn enforces_code_ids () {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(mock_collections);
+        let collection = Addr::unchecked("coll_one");
+        let params = Params { allowed_code_ids: vec![1, 7], ..Params::default() };
+        assert!(verify_collection(deps.as_ref(), &collection, TokenStandard::Cw721, &params).is_ok());
+        let params = Params { allowed_code_ids: vec![7], ..Params::default() };
+        let err = verify_collection(deps.as_ref(), &collection, TokenStandard::Cw721, &params).unwrap_err();
+        assert_eq!(ContractError::CodeIdNotAllowed { code_id: 1 }, err);
+    }
+}
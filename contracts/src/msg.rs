@@ -1,28 +1,115 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
-use crate::state::{CollectionData, Collection, OfferData};
+use cosmwasm_std::{Addr, Binary, Uint128, Timestamp};
+use crate::state::{
+    AccessMode, AutoRelend, CollectionData, Collection, FeeConfig, Installment, InterestMode, LatePenalty, OfferData,
+    Params, Operation, Role, Status, TokenStandard, Valuation
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
-    pub founder_one: Addr,
-    pub founder_two: Addr,
+    pub founder_one: String,
+    pub founder_two: String,
+    /// Granted the Guardian role, the sender becomes Admin
+    pub guardian: Option<String>,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
     AddCollection {
-        collection_address: Addr,
-        apy: Uint128,
+        collection_address: String,
+        /// Yearly rate in basis points (`750` = 7.5%)
+        apy_bps: u32,
+        /// Defaults to cw721
+        standard: Option<TokenStandard>,
+        /// Defaults to compound
+        interest_mode: Option<InterestMode>,
+    },
+
+    SetValuation {
+        collection_id: Uint128,
+        valuation: Option<Valuation>,
+    },
+
+    /// Accepts late payments for `window` seconds past their due date, with a
+    /// penalty. `None` defaults loans as soon as a payment is missed.
+    SetLatePenalty {
+        collection_id: Uint128,
+        penalty: Option<LatePenalty>,
+    },
+
+    SetAccessMode {
+        collection_id: Uint128,
+        mode: AccessMode,
+    },
+
+    UpdateAllowlist {
+        collection_id: Uint128,
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+
+    UpdateBlocklist {
+        collection_id: Uint128,
+        block: Vec<String>,
+        unblock: Vec<String>,
+    },
+
+    GrantRole {
+        address: String,
+        role: Role,
+    },
+
+    RevokeRole {
+        address: String,
+        role: Role,
+    },
+
+    UpdateFees {
+        founder_fee_bps: u16,
+        /// Cut of late payment penalties per founder, unchanged when `None`
+        penalty_fee_bps: Option<u16>,
+    },
+
+    /// Pauses the given operations, or all of them when `None`
+    Pause {
+        operations: Option<Vec<Operation>>,
+    },
+
+    Unpause {
+        operations: Option<Vec<Operation>>,
+    },
+
+    SetEmergency {
+        enabled: bool,
     },
 
     Lend {
         collection_id: Uint128, 
         duration: Uint128, 
+        /// Opens a new offer with the repaid funds on the same terms
+        auto_relend: Option<AutoRelend>,
+    },
+
+    /// Changes the terms of an open offer. A larger amount is paid for with
+    /// the difference plus founder fees, a smaller one refunds the difference.
+    UpdateOffer {
+        collection_id: Uint128,
+        offer_id: Uint128,
+        new_amount: Option<Uint128>,
+        new_duration: Option<Uint128>,
+        /// Yearly rate in basis points the interest is recomputed with, 1 to `MAX_APY_BPS`
+        new_interest: Option<u32>,
+    },
+
+    /// Returns the repaid funds of an offer to the lender from now on
+    StopAutoRelend {
+        collection_id: Uint128,
+        offer_id: Uint128,
     },
 
     Borrow {
         collection_id: Uint128, 
-        token_id: Uint128,
+        token_id: String,
     },
 
     Repay {
@@ -35,6 +122,56 @@ pub enum ExecuteMsg {
         offer_id: Uint128, 
     },
 
+    /// Borrows from `collection_id` against several cw721 tokens at once,
+    /// at least one of them from `collection_id`
+    BorrowBundle {
+        collection_id: Uint128,
+        tokens: Vec<BundleToken>,
+    },
+
+    /// Fills several offers against one cw721 token. Repayment is split between
+    /// the lenders, on default the senior lender claims the token.
+    BorrowAggregated {
+        collection_id: Uint128,
+        token_id: String,
+        fill: Fill,
+    },
+
+    /// Takes the best offer against one cw721 token, repaid in `installments`
+    /// equal periods over the offer's duration, at most `MAX_INSTALLMENTS`
+    BorrowInstallments {
+        collection_id: Uint128,
+        token_id: String,
+        installments: u32,
+    },
+
+    /// Pays the next installment of a loan taken with `BorrowInstallments`
+    PayInstallment {
+        collection_id: Uint128,
+        offer_id: Uint128,
+    },
+
+    /// Several `Lend`s at once, the funds sent must add up to the item amounts
+    BatchLend {
+        items: Vec<LendItem>,
+    },
+
+    BatchWithdraw {
+        offers: Vec<OfferRef>,
+    },
+
+    /// Several `Repay`s at once, the funds sent must add up to the item amounts
+    BatchRepay {
+        items: Vec<RepayItem>,
+    },
+
+    BatchClaim {
+        offers: Vec<OfferRef>,
+    },
+
+    /// cw1155 collateral, sent by the collection with a `Cw1155HookMsg`
+    Receive(Cw1155ReceiveMsg),
+
     Withdraw {
         collection_id: Uint128, 
         offer_id: Uint128, 
@@ -50,19 +187,388 @@ pub enum QueryMsg {
     #[returns(Uint128)]
     GetBestOffer {
         collection_id: Uint128,
-        from: Addr
+        from: String
     },
 
     #[returns(OfferData)]
     GetOffers {
         collection_id: Uint128,
-        from: Addr
+        from: String
     },
 
     #[returns(CollectionData)]
     GetCollectionData {
         collection_id: Uint128,
-        from: Addr
-    }
+        from: String
+    },
+
+    #[returns(PauseStatusResponse)]
+    PauseStatus {},
+
+    #[returns(RolesResponse)]
+    Roles {
+        address: String,
+    },
+
+    #[returns(FeeConfig)]
+    Fees {},
+
+    #[returns(Params)]
+    Params {},
+
+    #[returns(IsAllowedResponse)]
+    IsAllowed {
+        collection_id: Uint128,
+        address: String,
+    },
+
+    #[returns(BlockedTokensResponse)]
+    BlockedTokens {
+        collection_id: Uint128,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Lifetime loan figures of a collection
+    #[returns(CollectionStatsResponse)]
+    CollectionStats {
+        collection_id: Uint128,
+    },
+
+    /// Taken or defaulted loans maturing between `from` and `to` inclusive,
+    /// earliest first
+    #[returns(LoansExpiringResponse)]
+    LoansExpiring {
+        from: Timestamp,
+        to: Timestamp,
+        collection_id: Option<Uint128>,
+        limit: Option<u32>,
+        start_after: Option<OfferRef>,
+    },
+
+    /// Offers and loans of `address` in every collection
+    #[returns(PortfolioResponse)]
+    Portfolio {
+        address: String,
+    },
+
+    /// Due dates and amounts of a loan, a single payment for bullet loans
+    #[returns(LoanScheduleResponse)]
+    LoanSchedule {
+        collection_id: Uint128,
+        offer_id: Uint128,
+    },
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
+#[cw_serde]
+pub enum SudoMsg {
+    UpdateParams {
+        founder_fee_bps: Option<u16>,
+        offer_ttl: Option<u64>,
+        keeper_batch_size: Option<u32>,
+        allowed_code_ids: Option<Vec<u64>>,
+    },
+
+    ForcePause {
+        operations: Option<Vec<Operation>>,
+    },
+
+    ForceUnpause {
+        operations: Option<Vec<Operation>>,
+    },
+
+    ForceEmergency {
+        enabled: bool,
+    },
+
+    /// Sent every block by Injective to registered contracts
+    BeginBlocker {},
+}
+
+#[cw_serde]
+pub enum Fill {
+    /// Exactly these offers, the first one is the senior lender's
+    OfferIds(Vec<Uint128>),
+    /// The best offers until at least this amount is lent, the largest is senior
+    TargetAmount(Uint128),
+}
+
+#[cw_serde]
+pub struct LendItem {
+    pub collection_id: Uint128,
+    pub duration: Uint128,
+    /// INJ deposited for this offer, founder fees included
+    pub amount: Uint128,
+    pub auto_relend: Option<AutoRelend>,
+}
+
+#[cw_serde]
+pub struct RepayItem {
+    pub collection_id: Uint128,
+    pub offer_id: Uint128,
+    /// INJ paid towards this loan
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct OfferRef {
+    pub collection_id: Uint128,
+    pub offer_id: Uint128,
+}
+
+#[cw_serde]
+pub struct BundleToken {
+    pub collection_id: Uint128,
+    pub token_id: String,
+}
+
+/// Payload of a cw1155 transfer to this contract
+#[cw_serde]
+pub enum Cw1155HookMsg {
+    /// Takes the best offer with the received tokens as collateral
+    Borrow {
+        collection_id: Uint128,
+    },
+}
+
+/// Subset of the cw1155 interface the bank calls and is called with
+#[cw_serde]
+pub struct Cw1155ReceiveMsg {
+    pub operator: String,
+    /// `None` when the tokens were minted directly to the bank
+    pub from: Option<String>,
+    pub token_id: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+#[cw_serde]
+pub enum Cw1155ExecuteMsg {
+    BatchSendFrom {
+        from: String,
+        to: String,
+        batch: Vec<(String, Uint128)>,
+        msg: Option<Binary>,
+    },
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum Cw1155QueryMsg {
+    #[returns(Cw1155TokensResponse)]
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub struct Cw1155TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+/// Query interface expected from `PriceSource::Oracle` contracts
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    #[returns(FloorPriceResponse)]
+    FloorPrice {
+        collection: String,
+    },
+}
+
+#[cw_serde]
+pub struct FloorPriceResponse {
+    pub price: Uint128,
+}
+
+#[cw_serde]
+pub struct PauseStatusResponse {
+    pub paused: Vec<Operation>,
+    pub emergency: bool,
+}
+
+#[cw_serde]
+pub struct RolesResponse {
+    pub address: Addr,
+    pub roles: Vec<Role>,
+}
+
+/// Query interface expected from `AccessMode::Registry` contracts
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum RegistryQueryMsg {
+    #[returns(IsAllowedResponse)]
+    IsAllowed {
+        collection: String,
+        address: String,
+    },
+}
+
+#[cw_serde]
+pub struct IsAllowedResponse {
+    pub allowed: bool,
+}
+
+#[cw_serde]
+pub struct BlockedTokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[cw_serde]
+pub struct CollectionStatsResponse {
+    pub collection_id: Uint128,
+    /// Filled offers, an aggregated loan counts once per lender
+    pub loans_taken: u64,
+    pub volume_lent: Uint128,
+    pub repaid: u64,
+    pub defaulted: u64,
+    /// Late penalties excluded
+    pub interest_paid: Uint128,
+    /// Mean loan duration in seconds
+    pub average_duration: Uint128,
+    pub average_apy_bps: u32,
+    /// Principal of loans not yet repaid or claimed
+    pub outstanding: Uint128,
+}
+
+#[cw_serde]
+pub struct LoansExpiringResponse {
+    pub loans: Vec<ExpiringLoan>,
+}
+
+#[cw_serde]
+pub struct ExpiringLoan {
+    pub collection_id: Uint128,
+    pub offer_id: Uint128,
+    pub active_till: Timestamp,
+    /// When the lender can claim, after missed installments and the penalty window
+    pub claimable_at: Timestamp,
+    pub status: Status,
+    pub lender: Addr,
+    pub borrower: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct PortfolioResponse {
+    pub address: Addr,
+    /// Collections the address has open offers or active loans in
+    pub collections: Vec<CollectionPortfolio>,
+    pub principal_lent: Uint128,
+    pub principal_borrowed: Uint128,
+    pub interest_receivable: Uint128,
+    pub interest_payable: Uint128,
+    pub next_maturity: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct CollectionPortfolio {
+    pub collection_id: Uint128,
+    /// Open offers of the address
+    pub open_offers: Vec<Uint128>,
+    /// Funds waiting in `open_offers`
+    pub open_amount: Uint128,
+    /// Taken or defaulted loans the address lent
+    pub lending: Vec<Uint128>,
+    /// Taken or defaulted loans the address borrowed
+    pub borrowing: Vec<Uint128>,
+    /// Principal still to be repaid on `lending`
+    pub principal_lent: Uint128,
+    /// Principal still to be repaid on `borrowing`
+    pub principal_borrowed: Uint128,
+    pub interest_receivable: Uint128,
+    pub interest_payable: Uint128,
+    /// Next payment due on any of the loans
+    pub next_maturity: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct LoanScheduleResponse {
+    pub offer_id: Uint128,
+    pub installments: Vec<Installment>,
+    /// Principal and interest still to be paid
+    pub remaining: Uint128,
+}
+
+#[cw_serde]
+pub struct AddCollectionResponse {
+    pub collection_id: Uint128,
+}
+
+#[cw_serde]
+pub struct LendResponse {
+    pub offer_id: Uint128,
+}
+
+#[cw_serde]
+pub struct BorrowResponse {
+    /// Senior offer of an aggregated loan
+    pub offer_id: Uint128,
+    pub amount: Uint128,
+    pub interest: Uint128,
+    pub due: Timestamp,
+}
+
+#[cw_serde]
+pub struct RepayResponse {
+    pub offer_id: Uint128,
+    /// Includes `penalty`
+    pub amount_paid: Uint128,
+    pub penalty: Uint128,
+}
+
+#[cw_serde]
+pub struct PayInstallmentResponse {
+    pub offer_id: Uint128,
+    /// Index of the paid installment in the schedule
+    pub installment: u32,
+    /// Includes `penalty`
+    pub amount_paid: Uint128,
+    pub penalty: Uint128,
+    /// Whether this was the last installment and the collateral was returned
+    pub settled: bool,
+}
+
+#[cw_serde]
+pub struct ClaimResponse {
+    pub offer_id: Uint128,
+    pub token_id: Option<String>,
+}
+
+#[cw_serde]
+pub struct UpdateOfferResponse {
+    pub offer_id: Uint128,
+    pub amount: Uint128,
+    pub interest: Uint128,
+    pub duration: Uint128,
+}
+
+#[cw_serde]
+pub struct WithdrawResponse {
+    pub offer_id: Uint128,
+    pub amount: Uint128,
+}
+
+/// Results of a batch, in item order
+#[cw_serde]
+pub struct BatchLendResponse {
+    pub results: Vec<LendResponse>,
+}
+
+#[cw_serde]
+pub struct BatchWithdrawResponse {
+    pub results: Vec<WithdrawResponse>,
+}
+
+#[cw_serde]
+pub struct BatchRepayResponse {
+    pub results: Vec<RepayResponse>,
+}
+
+#[cw_serde]
+pub struct BatchClaimResponse {
+    pub results: Vec<ClaimResponse>,
+}
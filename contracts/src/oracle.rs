@@ -0,0 +1,117 @@
+use cosmwasm_std::{Addr, Deps, Uint128};
+
+use crate::error::ContractError;
+use crate::msg::{FloorPriceResponse, OracleQueryMsg};
+use crate::state::{PriceSource, Valuation, BPS_DENOMINATOR};
+
+/// Source of a collection's floor price, in `inj`.
+pub trait PriceOracle {
+    fn floor_price(&self, deps: Deps, collection: &Addr) -> Result<Uint128, ContractError>;
+}
+
+impl PriceOracle for PriceSource {
+    fn floor_price(&self, deps: Deps, collection: &Addr) -> Result<Uint128, ContractError> {
+        match self {
+            PriceSource::Fixed { price } => Ok(*price),
+            PriceSource::Oracle { contract } => {
+                let res: FloorPriceResponse = deps.querier.query_wasm_smart(
+                    contract,
+                    &OracleQueryMsg::FloorPrice {
+                        collection: collection.to_string(),
+                    },
+                )?;
+                Ok(res.price)
+            }
+        }
+    }
+}
+
+/// Largest loan the collateral supports: floor price × max LTV.
+pub fn max_loan(
+    oracle: &dyn PriceOracle,
+    deps: Deps,
+    collection: &Addr,
+    max_ltv_bps: u16,
+) -> Result<Uint128, ContractError> {
+    let floor = oracle.floor_price(deps, collection)?;
+    Ok(floor.multiply_ratio(max_ltv_bps, BPS_DENOMINATOR))
+}
+
+/// Loan cap for a collection, `None` when it has no valuation configured.
+pub fn loan_cap(
+    deps: Deps,
+    collection: &Addr,
+    valuation: &Option<Valuation>,
+) -> Result<Option<Uint128>, ContractError> {
+    match valuation {
+        Some(v) => Ok(Some(max_loan(&v.source, deps, collection, v.max_ltv_bps)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn check_ltv(amount: Uint128, cap: Option<Uint128>) -> Result<(), ContractError> {
+    match cap {
+        Some(max) if amount > max => Err(ContractError::LtvExceeded { amount, max }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::{from_json, to_json_binary, ContractResult, SystemResult, WasmQuery};
+
+    use super::*;
+
+    struct MockOracle(Uint128);
+
+    impl PriceOracle for MockOracle {
+        fn floor_price(&self, _deps: Deps, _collection: &Addr) -> Result<Uint128, ContractError> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn max_loan_applies_ltv () {
+        let deps = mock_dependencies();
+        let collection = Addr::unchecked("coll_one");
+        let oracle = MockOracle(Uint128::from(1000_u128));
+        assert_eq!(Uint128::from(500_u128), max_loan(&oracle, deps.as_ref(), &collection, 5000).unwrap());
+        assert_eq!(Uint128::from(1000_u128), max_loan(&oracle, deps.as_ref(), &collection, 10000).unwrap());
+        assert_eq!(Uint128::from(75_u128), max_loan(&oracle, deps.as_ref(), &collection, 750).unwrap());
+    }
+
+    #[test]
+    fn check_ltv_limits () {
+        assert!(check_ltv(Uint128::from(500_u128), Some(Uint128::from(500_u128))).is_ok());
+        assert!(check_ltv(Uint128::MAX, None).is_ok());
+        assert_eq!(
+            ContractError::LtvExceeded { amount: Uint128::from(501_u128), max: Uint128::from(500_u128) },
+            check_ltv(Uint128::from(501_u128), Some(Uint128::from(500_u128))).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn oracle_contract_source () {
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == "oracle" => {
+                let OracleQueryMsg::FloorPrice { collection } = from_json(msg).unwrap();
+                let price = if collection == "coll_one" { 4000_u128 } else { 0 };
+                SystemResult::Ok(ContractResult::Ok(
+                    to_json_binary(&FloorPriceResponse { price: Uint128::from(price) }).unwrap(),
+                ))
+            }
+            _ => panic!("unexpected query"),
+        });
+        let source = PriceSource::Oracle { contract: Addr::unchecked("oracle") };
+        assert_eq!(
+            Uint128::from(4000_u128),
+            source.floor_price(deps.as_ref(), &Addr::unchecked("coll_one")).unwrap()
+        );
+        assert_eq!(
+            Uint128::zero(),
+            source.floor_price(deps.as_ref(), &Addr::unchecked("coll_two")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use cosmwasm_std::{Timestamp, Uint128};
+
+use crate::error::ContractError;
+use crate::interest::{bps_rate, interest};
+use crate::state::{Installment, InterestMode};
+
+/// Most payments a loan can be split into, the schedule is stored on the offer.
+pub const MAX_INSTALLMENTS: u32 = 120;
+
+/// Splits a loan of `amount` over `duration` seconds into `installments` equal
+/// periods. Each payment returns an equal share of the principal, the last one
+/// the rounding remainder, plus the interest accrued on the outstanding
+/// principal over its period.
+pub fn amortize(
+    mode: InterestMode,
+    apy_bps: u32,
+    amount: Uint128,
+    start: Timestamp,
+    duration: Uint128,
+    installments: u32,
+) -> Result<Vec<Installment>, ContractError> {
+    let count = Uint128::from(installments);
+    if installments == 0 || installments > MAX_INSTALLMENTS || duration < count {
+        return Err(ContractError::InvalidInstallments { installments });
+    }
+    let period = duration / count;
+    let period_secs = u64::try_from(period.u128()).map_err(|_| ContractError::InvalidDuration { duration })?;
+    let share = amount / count;
+
+    let mut outstanding = amount;
+    let mut due = start;
+    let mut schedule = Vec::with_capacity(installments as usize);
+    for n in 1..=installments {
+        let principal = if n == installments { outstanding } else { share };
+        // the last period absorbs what the division left over
+        let secs = if n == installments {
+            u64::try_from((duration - period * (count - Uint128::one())).u128())
+                .map_err(|_| ContractError::InvalidDuration { duration })?
+        } else {
+            period_secs
+        };
+        due = due
+            .nanos()
+            .checked_add(secs.checked_mul(1_000_000_000).ok_or(ContractError::InvalidDuration { duration })?)
+            .map(Timestamp::from_nanos)
+            .ok_or(ContractError::InvalidDuration { duration })?;
+        schedule.push(Installment {
+            due,
+            principal,
+            interest: interest(mode, bps_rate(apy_bps), Uint128::from(secs), outstanding)?,
+            paid: false,
+        });
+        outstanding -= principal;
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_principal () {
+        let start = Timestamp::from_seconds(1000);
+        let schedule = amortize(InterestMode::Simple, 1000, Uint128::from(1000_u128), start, Uint128::from(301_u128), 3).unwrap();
+        let principals: Vec<u128> = schedule.iter().map(|i| i.principal.u128()).collect();
+        assert_eq!(vec![333, 333, 334], principals);
+        let due: Vec<u64> = schedule.iter().map(|i| i.due.seconds()).collect();
+        assert_eq!(vec![1100, 1200, 1301], due);
+        assert!(schedule.iter().all(|i| !i.paid));
+    }
+
+    #[test]
+    fn interest_on_outstanding () {
+        let year = Uint128::from(crate::interest::SECONDS_PER_YEAR);
+        let schedule = amortize(InterestMode::Simple, 1000, Uint128::from(1_000_000_u128), Timestamp::from_seconds(0), year, 2).unwrap();
+        // 10% a year: half a year on 1_000_000, then on 500_000
+        assert_eq!(50_000, schedule[0].interest.u128());
+        assert_eq!(25_000, schedule[1].interest.u128());
+    }
+
+    #[test]
+    fn invalid_counts () {
+        let amortize_in = |n| amortize(InterestMode::Compound, 1000, Uint128::from(100_u128), Timestamp::from_seconds(0), Uint128::from(10_u128), n);
+        assert_eq!(ContractError::InvalidInstallments { installments: 0 }, amortize_in(0).unwrap_err());
+        assert_eq!(ContractError::InvalidInstallments { installments: 11 }, amortize_in(11).unwrap_err());
+        assert_eq!(10, amortize_in(10).unwrap().len());
+
+        let year = Uint128::from(crate::interest::SECONDS_PER_YEAR);
+        let amortize_over_year = |n| amortize(InterestMode::Simple, 1000, Uint128::from(1_000_000_u128), Timestamp::from_seconds(0), year, n);
+        assert_eq!(MAX_INSTALLMENTS as usize, amortize_over_year(MAX_INSTALLMENTS).unwrap().len());
+        assert_eq!(
+            ContractError::InvalidInstallments { installments: MAX_INSTALLMENTS + 1 },
+            amortize_over_year(MAX_INSTALLMENTS + 1).unwrap_err()
+        );
+    }
+}
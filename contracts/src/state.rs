@@ -1,21 +1,241 @@
-use cosmwasm_std::{Addr, Uint128, Timestamp};
+use cosmwasm_std::{Addr, Empty, Uint128, Timestamp};
 use cw_storage_plus::{Map, Item};
 use cosmwasm_schema::cw_serde;
+use std::fmt;
 
 #[cw_serde]
 pub enum Status {
     Open,
     Taken,
     Paid,
-    Failed,
-    Done,
-    Cancelled
+    // stored as `failed` before the lifecycle was formalized, `migrate`
+    // closes those loans
+    #[serde(alias = "failed")]
+    Defaulted,
+    #[serde(alias = "done")]
+    Closed,
+    Cancelled,
+    Expired
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Status::Open => "open",
+            Status::Taken => "taken",
+            Status::Paid => "paid",
+            Status::Defaulted => "defaulted",
+            Status::Closed => "closed",
+            Status::Cancelled => "cancelled",
+            Status::Expired => "expired",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cw_serde]
+#[derive(Copy)]
+pub enum Operation {
+    Lend,
+    Borrow,
+    Repay,
+    Claim,
+    Withdraw
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::Lend,
+        Operation::Borrow,
+        Operation::Repay,
+        Operation::Claim,
+        Operation::Withdraw,
+    ];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Lend => "lend",
+            Operation::Borrow => "borrow",
+            Operation::Repay => "repay",
+            Operation::Claim => "claim",
+            Operation::Withdraw => "withdraw",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseState {
+    pub paused: Vec<Operation>,
+    /// Only lets lenders withdraw open offers and borrowers repay
+    pub emergency: bool,
+}
+
+impl PauseState {
+    pub fn allows(&self, operation: Operation) -> bool {
+        if self.paused.contains(&operation) {
+            return false;
+        }
+        !self.emergency || matches!(operation, Operation::Withdraw | Operation::Repay)
+    }
 }
 
+#[cw_serde]
+#[derive(Copy)]
+pub enum Role {
+    /// Lists collections and manages roles, implicitly holds every other role
+    Admin,
+    /// Manages collection valuations
+    RiskManager,
+    /// Manages protocol fees
+    FeeManager,
+    /// Pauses operations and toggles emergency mode
+    Guardian
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Admin => "admin",
+            Role::RiskManager => "risk_manager",
+            Role::FeeManager => "fee_manager",
+            Role::Guardian => "guardian",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cw_serde]
+pub struct FeeConfig {
+    /// Commission on each deposit paid to each founder, in basis points
+    pub founder_fee_bps: u16,
+    /// Cut of each late payment penalty paid to each founder, in basis points
+    #[serde(default)]
+    pub penalty_fee_bps: u16,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        FeeConfig { founder_fee_bps: 50, penalty_fee_bps: 0 }
+    }
+}
+
+#[cw_serde]
+pub struct Params {
+    /// Seconds an offer stays open before the keeper expires it, 0 disables expiry
+    pub offer_ttl: u64,
+    /// Offers the begin blocker looks at per block
+    pub keeper_batch_size: u32,
+    /// Code ids a collection must be instantiated from to be listed, empty allows any
+    #[serde(default)]
+    pub allowed_code_ids: Vec<u64>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            offer_ttl: 0,
+            keeper_batch_size: 20,
+            allowed_code_ids: vec![],
+        }
+    }
+}
+
+pub const BPS_DENOMINATOR: u128 = 10_000;
+/// Highest yearly rate a collection or offer can charge, 1000%
+pub const MAX_APY_BPS: u32 = 100_000;
+
 #[cw_serde]
 pub struct Collection {
     pub addr: Addr,
-    pub apy: Uint128,
+    /// cw721 name and symbol, read from the contract when it is listed
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub symbol: String,
+    /// Yearly rate in basis points (`750` = 7.5%)
+    pub apy_bps: u32,
+    #[serde(default)]
+    pub valuation: Option<Valuation>,
+    #[serde(default)]
+    pub access: AccessMode,
+    #[serde(default)]
+    pub standard: TokenStandard,
+    #[serde(default)]
+    pub interest_mode: InterestMode,
+    /// Grace period after a missed payment, `None` defaults loans as soon as they are due
+    #[serde(default)]
+    pub late_penalty: Option<LatePenalty>,
+}
+
+#[cw_serde]
+pub struct LatePenalty {
+    /// Seconds after a payment is due during which it is still accepted
+    pub window: u64,
+    /// Yearly penalty rate in basis points, accrued per second on the overdue principal
+    pub apr_bps: u32,
+}
+
+/// How `Collection.apy_bps` accrues over a loan's duration
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum InterestMode {
+    /// amount × rate × years
+    Simple,
+    /// amount × ((1 + rate)^years − 1)
+    #[default]
+    Compound,
+    /// amount × (e^(rate × years) − 1), accrued per second
+    Continuous,
+}
+
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum TokenStandard {
+    /// Non-fungible, borrowed against with `Borrow`
+    #[default]
+    Cw721,
+    /// Semi-fungible, borrowed against by sending tokens with a `Cw1155HookMsg`
+    Cw1155,
+}
+
+impl fmt::Display for TokenStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenStandard::Cw721 => "cw721",
+            TokenStandard::Cw1155 => "cw1155",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum AccessMode {
+    #[default]
+    Open,
+    /// Only addresses in `ALLOWLIST` for the collection
+    Allowlist,
+    /// Contract answering `RegistryQueryMsg::IsAllowed`
+    Registry { contract: Addr },
+}
+
+#[cw_serde]
+pub enum PriceSource {
+    /// Floor price set by the owner
+    Fixed { price: Uint128 },
+    /// Contract answering `OracleQueryMsg::FloorPrice`
+    Oracle { contract: Addr },
+}
+
+#[cw_serde]
+pub struct Valuation {
+    pub source: PriceSource,
+    /// Maximum loan to floor price ratio in basis points
+    pub max_ltv_bps: u16,
 }
 
 #[cw_serde]
@@ -24,11 +244,124 @@ pub struct Offer {
     pub amount: Uint128,
     pub active_till: Timestamp,
     pub interest: Uint128,
+    /// Collection rate `interest` was computed with, in basis points
+    #[serde(default)]
+    pub apy_bps: u32,
     pub lender: Addr,
     pub borrower: Option<Addr>,
-    pub token_id: Option<Uint128>,
+    /// cw721 token id of the collateral, see `migrate` for ids stored as `Uint128`
+    pub token_id: Option<String>,
+    /// Units of a cw1155 collateral token, `None` for cw721
+    #[serde(default)]
+    pub token_amount: Option<Uint128>,
+    /// cw721 tokens pledged alongside `token_id`, possibly from other collections
+    #[serde(default)]
+    pub bundle: Vec<BundleItem>,
+    /// Offers filled together against the same collateral, the first one is the
+    /// senior lender's. Empty for single-offer loans.
+    #[serde(default)]
+    pub syndicate: Vec<Uint128>,
+    /// Payments of an installment loan in due order, empty for bullet loans
+    #[serde(default)]
+    pub schedule: Vec<Installment>,
+    /// Collection penalty terms when the loan was taken
+    #[serde(default)]
+    pub late_penalty: Option<LatePenalty>,
+    /// Lends the repaid principal out again instead of returning it to the lender
+    #[serde(default)]
+    pub auto_relend: Option<AutoRelend>,
     pub status: Status,
     pub duration: Uint128,
+    #[serde(default)]
+    pub history: Vec<Transition>,
+}
+
+/// Settled through `Repay` only, installments are paid out as they come in.
+#[cw_serde]
+pub struct AutoRelend {
+    /// Times the funds are lent out again, counting down with each new offer
+    pub cycles: u32,
+    /// Relend the interest along with the principal
+    pub with_interest: bool,
+}
+
+#[cw_serde]
+pub struct Installment {
+    pub due: Timestamp,
+    pub principal: Uint128,
+    pub interest: Uint128,
+    pub paid: bool,
+}
+
+#[cw_serde]
+pub struct BundleItem {
+    pub collection: Addr,
+    pub token_id: String,
+}
+
+impl Offer {
+    /// Block time at which the offer entered `status`, if it ever did.
+    pub fn transitioned_at(&self, status: &Status) -> Option<Timestamp> {
+        self.history
+            .iter()
+            .find(|t| &t.status == status)
+            .map(|t| t.at)
+    }
+
+    /// Index of the first unpaid installment.
+    pub fn next_installment(&self) -> Option<usize> {
+        self.schedule.iter().position(|i| !i.paid)
+    }
+
+    /// The collateral is held by the contract, the loan is taken or defaulted
+    /// and not yet repaid or claimed.
+    pub fn in_escrow(&self) -> bool {
+        self.status == Status::Taken || self.status == Status::Defaulted
+    }
+
+    /// Time by which the borrower must pay next, after which the loan can be claimed.
+    pub fn due(&self) -> Timestamp {
+        match self.next_installment() {
+            Some(i) => self.schedule[i].due,
+            None => self.active_till,
+        }
+    }
+
+    /// Time after which the loan can be claimed, the end of the penalty window
+    /// for the next payment.
+    pub fn grace_end(&self) -> Timestamp {
+        let due = self.due();
+        match &self.late_penalty {
+            Some(penalty) => Timestamp::from_nanos(due.nanos().saturating_add(penalty.window.saturating_mul(1_000_000_000))),
+            None => due,
+        }
+    }
+
+    /// Principal not yet paid back.
+    pub fn principal_owed(&self) -> Uint128 {
+        if self.schedule.is_empty() {
+            return self.amount;
+        }
+        self.schedule.iter().filter(|i| !i.paid).map(|i| i.principal).fold(Uint128::zero(), Uint128::saturating_add)
+    }
+
+    /// What the borrower still owes: every unpaid installment, or principal
+    /// plus interest of a bullet loan.
+    pub fn owed(&self) -> Option<Uint128> {
+        if self.schedule.is_empty() {
+            return self.amount.checked_add(self.interest).ok();
+        }
+        self.schedule
+            .iter()
+            .filter(|i| !i.paid)
+            .try_fold(Uint128::zero(), |sum, i| sum.checked_add(i.principal).ok()?.checked_add(i.interest).ok())
+    }
+}
+
+#[cw_serde]
+pub struct Transition {
+    pub status: Status,
+    pub at: Timestamp,
 }
 
 #[cw_serde]
@@ -40,6 +373,27 @@ pub struct CollectionData {
     pub total_pool: Uint128
 }
 
+/// Lifetime loan figures of a collection, kept up to date by the handlers.
+/// Each filled offer counts as one loan, so an aggregated loan counts once
+/// per lender.
+#[cw_serde]
+#[derive(Default)]
+pub struct CollectionStats {
+    pub loans_taken: u64,
+    /// Principal handed to borrowers
+    pub volume_lent: Uint128,
+    pub repaid: u64,
+    pub defaulted: u64,
+    /// Interest received from borrowers, late penalties excluded
+    pub interest_paid: Uint128,
+    /// Sum of loan durations in seconds, for the average
+    pub total_duration: Uint128,
+    /// Sum of loan rates in basis points, for the average
+    pub total_apy_bps: Uint128,
+    /// Principal of taken and defaulted loans not yet repaid or claimed
+    pub outstanding: Uint128,
+}
+
 #[cw_serde]
 pub struct OfferData {
     pub lent: Vec<Offer>,
@@ -57,3 +411,41 @@ pub const OFFERS: Map<Addr, Vec<Offer>> = Map::new(OFFERS_KEY);
 
 pub const FOUNDERS_KEY: &str = "founders";
 pub const FOUNDERS: Item<Vec<Addr>> = Item::new(FOUNDERS_KEY);
+
+pub const ROLES_KEY: &str = "roles";
+pub const ROLES: Map<&Addr, Vec<Role>> = Map::new(ROLES_KEY);
+
+pub const FEES_KEY: &str = "fees";
+pub const FEES: Item<FeeConfig> = Item::new(FEES_KEY);
+
+pub const PAUSE_KEY: &str = "pause";
+pub const PAUSE: Item<PauseState> = Item::new(PAUSE_KEY);
+
+pub const PARAMS_KEY: &str = "params";
+pub const PARAMS: Item<Params> = Item::new(PARAMS_KEY);
+
+pub const ALLOWLIST_KEY: &str = "allowlist";
+pub const ALLOWLIST: Map<(u128, &Addr), Empty> = Map::new(ALLOWLIST_KEY);
+
+/// Flagged token ids per collection, keyed by the cw721 token id string
+pub const BLOCKLIST_KEY: &str = "blocklist";
+pub const BLOCKLIST: Map<(u128, &str), Empty> = Map::new(BLOCKLIST_KEY);
+
+/// Taken and defaulted loans until they close, keyed by `active_till` in
+/// nanoseconds, collection id and offer id
+pub const MATURITIES_KEY: &str = "maturities";
+pub const MATURITIES: Map<(u64, u128, u128), Empty> = Map::new(MATURITIES_KEY);
+
+/// Open offers until they are taken, withdrawn or expire, keyed by the time
+/// they were listed in nanoseconds, collection id and offer id
+pub const OPEN_OFFERS_KEY: &str = "open_offers";
+pub const OPEN_OFFERS: Map<(u64, u128, u128), Empty> = Map::new(OPEN_OFFERS_KEY);
+
+/// Taken loans until they are settled or default, keyed by `grace_end` in
+/// nanoseconds, collection id and offer id
+pub const GRACE_ENDS_KEY: &str = "grace_ends";
+pub const GRACE_ENDS: Map<(u64, u128, u128), Empty> = Map::new(GRACE_ENDS_KEY);
+
+/// Collection id -> lifetime loan figures
+pub const STATS_KEY: &str = "stats";
+pub const STATS: Map<u128, CollectionStats> = Map::new(STATS_KEY);
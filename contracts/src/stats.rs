@@ -0,0 +1,106 @@
+use cosmwasm_std::{Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::state::{CollectionStats, Offer, Status, STATS};
+
+// Handlers record each loan event as it happens. `replay` derives the same
+// figures from an offer's stored state as far as it can, which backfills
+// collections that have none on migration.
+
+/// Counts a loan that was just taken.
+pub fn record_taken(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) -> Result<(), ContractError> {
+    update(storage, collection_id, |stats| taken(stats, offer))
+}
+
+/// Records principal and interest received from a borrower.
+pub fn record_payment(
+    storage: &mut dyn Storage,
+    collection_id: Uint128,
+    principal: Uint128,
+    interest: Uint128,
+) -> Result<(), ContractError> {
+    update(storage, collection_id, |stats| paid(stats, principal, interest))
+}
+
+/// Counts a loan paid in full.
+pub fn record_repaid(storage: &mut dyn Storage, collection_id: Uint128) -> Result<(), ContractError> {
+    update(storage, collection_id, |stats| {
+        stats.repaid += 1;
+        Ok(())
+    })
+}
+
+/// Counts a loan the borrower failed to pay back.
+pub fn record_default(storage: &mut dyn Storage, collection_id: Uint128) -> Result<(), ContractError> {
+    update(storage, collection_id, |stats| {
+        stats.defaulted += 1;
+        Ok(())
+    })
+}
+
+/// Drops the unpaid principal of a defaulted loan once its collateral is claimed.
+pub fn record_claim(storage: &mut dyn Storage, collection_id: Uint128, offer: &Offer) -> Result<(), ContractError> {
+    update(storage, collection_id, |stats| {
+        stats.outstanding = stats.outstanding.saturating_sub(offer.principal_owed());
+        Ok(())
+    })
+}
+
+/// Adds what `offer` contributed to the figures so far.
+pub fn replay(stats: &mut CollectionStats, offer: &Offer) -> Result<(), ContractError> {
+    // offers get a borrower when they are taken and keep it
+    if offer.borrower.is_none() {
+        return Ok(());
+    }
+    // baseline loans settled before history was recorded, closed by `migrate`
+    if offer.status == Status::Closed && offer.transitioned_at(&Status::Taken).is_none() {
+        return Ok(());
+    }
+    taken(stats, offer)?;
+    let repaid = offer.status == Status::Paid || offer.transitioned_at(&Status::Paid).is_some();
+    if offer.schedule.is_empty() {
+        if repaid {
+            paid(stats, offer.amount, offer.interest)?;
+        }
+    } else {
+        for installment in offer.schedule.iter().filter(|i| i.paid) {
+            paid(stats, installment.principal, installment.interest)?;
+        }
+    }
+    if repaid {
+        stats.repaid += 1;
+    }
+    if offer.status == Status::Defaulted || offer.transitioned_at(&Status::Defaulted).is_some() {
+        stats.defaulted += 1;
+        if offer.status == Status::Closed {
+            stats.outstanding = stats.outstanding.saturating_sub(offer.principal_owed());
+        }
+    }
+    Ok(())
+}
+
+fn taken(stats: &mut CollectionStats, offer: &Offer) -> Result<(), ContractError> {
+    let overflow = |_| ContractError::InterestOverflow {};
+    stats.loans_taken += 1;
+    stats.volume_lent = stats.volume_lent.checked_add(offer.amount).map_err(overflow)?;
+    stats.total_duration = stats.total_duration.checked_add(offer.duration).map_err(overflow)?;
+    stats.total_apy_bps = stats.total_apy_bps.checked_add(Uint128::from(offer.apy_bps)).map_err(overflow)?;
+    stats.outstanding = stats.outstanding.checked_add(offer.amount).map_err(overflow)?;
+    Ok(())
+}
+
+fn paid(stats: &mut CollectionStats, principal: Uint128, interest: Uint128) -> Result<(), ContractError> {
+    stats.interest_paid = stats.interest_paid.checked_add(interest).map_err(|_| ContractError::InterestOverflow {})?;
+    stats.outstanding = stats.outstanding.saturating_sub(principal);
+    Ok(())
+}
+
+fn update(
+    storage: &mut dyn Storage,
+    collection_id: Uint128,
+    f: impl FnOnce(&mut CollectionStats) -> Result<(), ContractError>,
+) -> Result<(), ContractError> {
+    let mut stats = STATS.may_load(storage, collection_id.u128())?.unwrap_or_default();
+    f(&mut stats)?;
+    Ok(STATS.save(storage, collection_id.u128(), &stats)?)
+}